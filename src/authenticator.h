@@ -26,6 +26,25 @@ typedef enum ErrorCorrectionLevel {
   High,
 } ErrorCorrectionLevel;
 
+/**
+ * The HMAC hash function used to derive codes, as described in RFC 6238. Most authenticator
+ * apps default to `Sha1`, which is also the default of this crate.
+ */
+typedef enum Algorithm {
+  /**
+   * HMAC-SHA1, the default of RFC 4226 and of the Google Authenticator app.
+   */
+  Sha1,
+  /**
+   * HMAC-SHA256.
+   */
+  Sha256,
+  /**
+   * HMAC-SHA512.
+   */
+  Sha512,
+} Algorithm;
+
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
@@ -38,6 +57,7 @@ const char *create_secret(uint8_t len) CF_SWIFT_NAME(create_secret(len:));
 
 #if defined(DEFINE_QRCODE)
 /**
+ * # Safety
  * A function that can be used for convenient access to the function
  * `qr_code`, by providing a default of 200 to the `width` parameter, 200
  * to the `height` parameter, and `ErrorCorrectionLevel::Medium` to the `level` parameter.
@@ -50,6 +70,7 @@ const char *qr_code(const char *secret,
                     enum ErrorCorrectionLevel level) CF_SWIFT_NAME(qr_code(secret:name:title:witdh:height:level:));
 #endif
 
+#if defined(DEFINE_QRCODE_URL)
 /**
  * # Safety
  * A function that can be used for convenient access to the function
@@ -62,6 +83,7 @@ const char *qr_code_url(const char *secret,
                         uint32_t witdh,
                         uint32_t height,
                         enum ErrorCorrectionLevel level) CF_SWIFT_NAME(qr_code_url(secret:name:title:witdh:height:level:));
+#endif
 
 /**
  * # Safety
@@ -84,6 +106,56 @@ bool verify_code(const char *secret,
                  uint64_t discrepancy,
                  uint64_t time_slice) CF_SWIFT_NAME(verify_code(secret:code:discrepancy:time_slice:));
 
+/**
+ * # Safety
+ * A function that can be used for convenient access to the function
+ * `get_code`, using the hash function given in the `algorithm` parameter.
+ */
+const char *get_code_with_algorithm(const char *secret,
+                                    uint64_t time_slice,
+                                    enum Algorithm algorithm) CF_SWIFT_NAME(get_code_with_algorithm(secret:time_slice:algorithm:));
+
+/**
+ * # Safety
+ * A function that can be used for convenient access to the function
+ * `verify_code`, using the hash function given in the `algorithm` parameter.
+ */
+bool verify_code_with_algorithm(const char *secret,
+                                const char *code,
+                                uint64_t discrepancy,
+                                uint64_t time_slice,
+                                enum Algorithm algorithm) CF_SWIFT_NAME(verify_code_with_algorithm(secret:code:discrepancy:time_slice:algorithm:));
+
+#if defined(DEFINE_QRCODE_URL)
+/**
+ * # Safety
+ * A function that can be used for convenient access to the function
+ * `qr_code_url`, advertising the hash function given in the `algorithm` parameter.
+ */
+const char *qr_code_url_with_algorithm(const char *secret,
+                                       const char *name,
+                                       const char *title,
+                                       uint32_t witdh,
+                                       uint32_t height,
+                                       enum ErrorCorrectionLevel level,
+                                       enum Algorithm algorithm) CF_SWIFT_NAME(qr_code_url_with_algorithm(secret:name:title:witdh:height:level:algorithm:));
+#endif
+
+#if defined(DEFINE_QRCODE)
+/**
+ * # Safety
+ * A function that can be used for convenient access to the function
+ * `qr_code`, advertising the hash function given in the `algorithm` parameter.
+ */
+const char *qr_code_with_algorithm(const char *secret,
+                                   const char *name,
+                                   const char *title,
+                                   uint32_t witdh,
+                                   uint32_t height,
+                                   enum ErrorCorrectionLevel level,
+                                   enum Algorithm algorithm) CF_SWIFT_NAME(qr_code_with_algorithm(secret:name:title:witdh:height:level:algorithm:));
+#endif
+
 /**
  * # Safety
  * A function that can be used for free returnd to C string
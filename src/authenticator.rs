@@ -20,22 +20,37 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use hmacsha1::hmac_sha1;
-use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{error, fmt, result};
+use crate::{
+    Clock, OtpAuthUri, PreparedSecret, Rejection, Secret, SystemClock, ToPreparedSecret,
+    Verification, DEFAULT_SECRET_BITS,
+};
+#[cfg(feature = "with-qrcode")]
+use crate::{QrStyle, TerminalQr};
+use std::borrow::Cow;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{error, fmt, io, result};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::Zeroize;
 
-#[cfg(any(feature = "with-qrcode",doc))]
+#[cfg(any(feature = "qrcode", doc))]
+use base64::{engine::general_purpose::STANDARD, Engine};
+#[cfg(any(feature = "qrcode", doc))]
 use qrcode::render::svg;
-#[cfg(any(feature = "with-qrcode",doc))]
+#[cfg(any(feature = "qrcode", doc))]
 use qrcode::{EcLevel, QrCode};
 
-#[cfg(any(feature = "with-qrcode",doc))]
+#[cfg(any(feature = "qrcode", doc))]
 use qrcode::types::QrError;
 /// cbindgen:ignore
-const SECRET_MAX_LEN: usize = 128;
+pub(crate) const SECRET_MAX_BYTES: usize = 80;
 /// cbindgen:ignore
-const SECRET_MIN_LEN: usize = 16;
+pub(crate) const SECRET_MIN_BYTES: usize = 10;
+/// cbindgen:ignore
+pub(crate) const CODE_MAX_LEN: usize = 10;
+/// cbindgen:ignore
+pub(crate) const CODE_MIN_LEN: usize = 6;
 
 /// Controls the amount of fault tolerance that the QR code should accept. Require the feature
 /// flag `with-qrcode`.
@@ -67,7 +82,7 @@ impl fmt::Display for ErrorCorrectionLevel {
     }
 }
 
-#[cfg(any(feature = "with-qrcode", doc))]
+#[cfg(any(feature = "qrcode", doc))]
 impl From<ErrorCorrectionLevel> for qrcode::EcLevel {
     fn from(level: ErrorCorrectionLevel) -> Self {
         match level {
@@ -79,6 +94,44 @@ impl From<ErrorCorrectionLevel> for qrcode::EcLevel {
     }
 }
 
+/// The HMAC hash function used to derive codes, as described in RFC 6238. Most authenticator
+/// apps default to `Sha1`, which is also the default of this crate.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub enum Algorithm {
+    /// HMAC-SHA1, the default of RFC 4226 and of the Google Authenticator app.
+    #[default]
+    Sha1,
+    /// HMAC-SHA256.
+    Sha256,
+    /// HMAC-SHA512.
+    Sha512,
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match self {
+            Algorithm::Sha1 => "SHA1",
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha512 => "SHA512",
+        };
+        f.write_str(result)
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = GAError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "SHA1" => Ok(Algorithm::Sha1),
+            "SHA256" => Ok(Algorithm::Sha256),
+            "SHA512" => Ok(Algorithm::Sha512),
+            _ => Err(GAError::Error("algorithm must be SHA1, SHA256 or SHA512")),
+        }
+    }
+}
+
 /// cbindgen:ignore
 /// A list of all usable characters in base32.
 const ALPHABET: [char; 33] = [
@@ -90,11 +143,21 @@ const ALPHABET: [char; 33] = [
 /// with google authenticator.
 pub struct GoogleAuthenticator {
     code_len: usize,
+    algorithm: Algorithm,
+    period: u64,
+    epoch: u64,
+    clock: Arc<dyn Clock + Send + Sync>,
 }
 
 impl Default for GoogleAuthenticator {
     fn default() -> Self {
-        Self { code_len: 6 }
+        Self {
+            code_len: 6,
+            algorithm: Algorithm::default(),
+            period: 30,
+            epoch: 0,
+            clock: Arc::new(SystemClock),
+        }
     }
 }
 
@@ -113,7 +176,8 @@ impl GoogleAuthenticator {
         Self::default()
     }
 
-    /// Use this method to configure the length of the generated code.
+    /// Use this method to configure the length of the generated code. RFC 4226 allows 6 to 10
+    /// digits, other lengths make generating and verifying codes fail.
     ///
     /// ### Example
     /// ```rust
@@ -127,7 +191,71 @@ impl GoogleAuthenticator {
         self
     }
 
-    /// Create new secret.
+    /// Use this method to configure the hash algorithm used to generate and verify codes, and
+    /// that is advertised in the generated QR codes. Defaults to `Algorithm::Sha1`.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::{Algorithm, GoogleAuthenticator};
+    ///
+    /// let auth = GoogleAuthenticator::new()
+    ///     .with_algorithm(Algorithm::Sha256);
+    /// ```
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Use this method to configure the time step (X in RFC 6238) during which a code is valid.
+    /// Only whole seconds are used, and a period of zero makes generating and verifying codes
+    /// fail. Defaults to 30 seconds.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::GoogleAuthenticator;
+    /// use std::time::Duration;
+    ///
+    /// let auth = GoogleAuthenticator::new()
+    ///     .with_period(Duration::from_secs(60));
+    /// ```
+    pub fn with_period(mut self, period: Duration) -> Self {
+        self.period = period.as_secs();
+        self
+    }
+
+    /// Use this method to configure the unix time from which time steps are counted (T0 in
+    /// RFC 6238). Defaults to 0, the unix epoch.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::GoogleAuthenticator;
+    ///
+    /// let auth = GoogleAuthenticator::new()
+    ///     .with_epoch(1_000_000_000);
+    /// ```
+    pub fn with_epoch(mut self, epoch: u64) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    /// Use this method to configure the clock that is used when a time slice of 0 is passed.
+    /// Defaults to `SystemClock`.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::{FixedClock, GoogleAuthenticator};
+    ///
+    /// let auth = GoogleAuthenticator::new()
+    ///     .with_clock(FixedClock::new(1523610659));
+    /// assert!(auth.verify_code("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", "224124", 3, 0));
+    /// ```
+    pub fn with_clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Create new secret of `length` base32 characters. Note that lengths which are not a multiple
+    /// of 8 can not always be decoded again, use `generate_secret` or `Secret::generate` instead.
     ///
     /// Example:
     /// ```rust
@@ -146,9 +274,26 @@ impl GoogleAuthenticator {
         secret.into_iter().collect()
     }
 
+    /// Create a new secret of 160 bits, as recommended by RFC 4226, from the random number
+    /// generator of the operating system. Use `Secret::generate` for other sizes.
+    ///
+    /// Example:
+    /// ```rust
+    /// use google_authenticator::GoogleAuthenticator;
+    ///
+    /// let google_authenticator = GoogleAuthenticator::new();
+    /// let secret = google_authenticator.generate_secret().unwrap();
+    /// assert_eq!(32, secret.expose_base32().len());
+    /// ```
+    pub fn generate_secret(&self) -> Result<Secret> {
+        Secret::generate(DEFAULT_SECRET_BITS)
+    }
+
     /// Calculate the code, with given secret and point in time. The `secret` parameter is the
-    /// secret configured for this user. The `times_slice` parameter is the unix timestamp divided
-    /// by 30 at which the code should expire.
+    /// secret configured for this user. The `times_slice` parameter is the number of periods
+    /// (30 seconds by default) that passed since the epoch (the unix epoch by default) at the time
+    /// the code should be valid. If 0 is provided here, the current time of the clock will be
+    /// used, use `get_code_at` for the code of the first time slice.
     ///
     /// ### Example
     /// ```rust
@@ -157,43 +302,69 @@ impl GoogleAuthenticator {
     /// let authenticator = GoogleAuthenticator::new();
     /// authenticator.get_code("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", 1523610659 / 30).unwrap();
     /// ```
-    pub fn get_code(&self, secret: &str, times_slice: u64) -> Result<String> {
-        if secret.len() < SECRET_MIN_LEN || secret.len() > SECRET_MAX_LEN {
+    pub fn get_code<S: ToPreparedSecret + ?Sized>(
+        &self,
+        secret: &S,
+        times_slice: u64,
+    ) -> Result<String> {
+        let message = self.time_slice(now_if_zero(times_slice))?;
+        self.get_hotp_code(secret, message)
+    }
+
+    /// Calculate the code of the given time slice, like `get_code`, except that a time slice of 0
+    /// is the first period after the epoch instead of the current time.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::GoogleAuthenticator;
+    ///
+    /// let authenticator = GoogleAuthenticator::new();
+    /// let code = authenticator
+    ///     .get_code_at("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", 0)
+    ///     .unwrap();
+    /// assert_eq!("755224", code);
+    /// ```
+    pub fn get_code_at<S: ToPreparedSecret + ?Sized>(
+        &self,
+        secret: &S,
+        time_slice: u64,
+    ) -> Result<String> {
+        let message = self.time_slice(Some(time_slice))?;
+        self.get_hotp_code(secret, message)
+    }
+
+    /// Calculate the counter based code (HOTP, RFC 4226) for the given secret. The `counter` is
+    /// the moving factor that is shared between the token and the server and that is incremented
+    /// every time a code is generated on the token.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::GoogleAuthenticator;
+    ///
+    /// let authenticator = GoogleAuthenticator::new();
+    /// authenticator.get_hotp_code("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", 42).unwrap();
+    /// ```
+    pub fn get_hotp_code<S: ToPreparedSecret + ?Sized>(
+        &self,
+        secret: &S,
+        counter: u64,
+    ) -> Result<String> {
+        let secret = secret.to_prepared_secret(self.algorithm)?;
+        if self.code_len < CODE_MIN_LEN || self.code_len > CODE_MAX_LEN {
             return Err(GAError::Error(
-                "bad secret length. must be less than 128 and more than 16, recommend 32",
+                "bad code length. must be between 6 and 10 digits",
             ));
         }
-
-        let message = if times_slice == 0 {
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                / 30
-        } else {
-            times_slice
-        };
-        let key = Self::base32_decode(secret)?;
-        let msg_bytes = message.to_be_bytes();
-        let hash = hmac_sha1(&key, &msg_bytes);
-        let offset = hash[hash.len() - 1] & 0x0F;
-        let mut truncated_hash: [u8; 4] = Default::default();
-        truncated_hash.copy_from_slice(&hash[offset as usize..(offset + 4) as usize]);
-        let mut code = i32::from_be_bytes(truncated_hash);
-        code &= 0x7FFFFFFF;
-        code %= 1_000_000;
-        let mut code_str = code.to_string();
-        for i in 0..(self.code_len - code_str.len()) {
-            code_str.insert(i, '0');
-        }
-        Ok(code_str)
+        Ok(secret.hotp(counter, self.code_len))
     }
 
     /// This function verifies that a provided code is correct. The parameter `secret` is used to
     /// verify the user. `code` is the code that will be verified. The parameter `discrepancy`
-    /// indicates number of seconds ago that a code may be generated. `time_slice` is used to modify
-    /// what the current time is, as a unix timestamp. If 0 is provided here, the current time will
-    /// be used.
+    /// indicates number of periods before and after the current one in which a code may have been
+    /// generated. `time_slice` is used to modify what the current time is, as the number of
+    /// periods since the epoch. If 0 is provided here, the current time of the clock will be used.
+    /// All codes in the window are computed and compared in constant time, so the time taken does
+    /// not depend on whether or where the code matched.
     ///
     /// ### Example
     /// ```rust
@@ -202,77 +373,325 @@ impl GoogleAuthenticator {
     /// let authenticator = GoogleAuthenticator::new();
     /// authenticator.verify_code("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", "224124", 3, 1523610659 / 30);
     /// ```
-    pub fn verify_code(&self, secret: &str, code: &str, discrepancy: u64, time_slice: u64) -> bool {
+    pub fn verify_code<S: ToPreparedSecret + ?Sized>(
+        &self,
+        secret: &S,
+        code: &str,
+        discrepancy: u64,
+        time_slice: u64,
+    ) -> bool {
+        self.verify_code_once(secret, code, discrepancy, time_slice, None)
+            .is_some()
+    }
+
+    /// This function verifies a code like `verify_code`, except that a `time_slice` of 0 is the
+    /// first period after the epoch instead of the current time.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::GoogleAuthenticator;
+    ///
+    /// let authenticator = GoogleAuthenticator::new();
+    /// assert!(authenticator.verify_code_at("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", "755224", 0, 0));
+    /// ```
+    pub fn verify_code_at<S: ToPreparedSecret + ?Sized>(
+        &self,
+        secret: &S,
+        code: &str,
+        discrepancy: u64,
+        time_slice: u64,
+    ) -> bool {
+        self.verify_detailed(secret, code, discrepancy, Some(time_slice), None)
+            .is_ok()
+    }
+
+    /// This function verifies a code like `verify_code`, but rejects codes that were already
+    /// accepted before, like the `DISALLOW_REUSE` option of libpam-google-authenticator.
+    /// `last_time_slice` is the time slice that was returned by the last successful verification
+    /// for this user, or `None` if there was none. Codes of that time slice or earlier ones are
+    /// rejected. On success, the matched time slice is returned, which has to be stored and passed
+    /// to the next verification.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::GoogleAuthenticator;
+    ///
+    /// let authenticator = GoogleAuthenticator::new();
+    /// let secret = "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3";
+    /// let time_slice = 1523610659 / 30;
+    /// let last = authenticator.verify_code_once(secret, "224124", 3, time_slice, None);
+    /// assert!(last.is_some());
+    /// assert!(authenticator.verify_code_once(secret, "224124", 3, time_slice, last).is_none());
+    /// ```
+    pub fn verify_code_once<S: ToPreparedSecret + ?Sized>(
+        &self,
+        secret: &S,
+        code: &str,
+        discrepancy: u64,
+        time_slice: u64,
+        last_time_slice: Option<u64>,
+    ) -> Option<u64> {
+        self.verify_code_detailed(secret, code, discrepancy, time_slice, last_time_slice)
+            .ok()
+            .map(|verification| verification.time_slice)
+    }
+
+    /// This function verifies a code like `verify_code_once`, but reports the details of the
+    /// outcome. On success, the matched time slice and its offset from the current time slice are
+    /// returned, which can be used to detect users with a wrong clock. Otherwise the reason of the
+    /// rejection is returned.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::{GoogleAuthenticator, Rejection};
+    ///
+    /// let authenticator = GoogleAuthenticator::new();
+    /// let secret = "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3";
+    /// let time_slice = 1523610659 / 30;
+    /// let verification = authenticator
+    ///     .verify_code_detailed(secret, "224124", 3, time_slice, None)
+    ///     .unwrap();
+    /// assert_eq!(time_slice, verification.time_slice);
+    /// assert_eq!(0, verification.offset);
+    /// assert_eq!(
+    ///     Err(Rejection::NonNumeric),
+    ///     authenticator.verify_code_detailed(secret, "22412a", 3, time_slice, None)
+    /// );
+    /// ```
+    pub fn verify_code_detailed<S: ToPreparedSecret + ?Sized>(
+        &self,
+        secret: &S,
+        code: &str,
+        discrepancy: u64,
+        time_slice: u64,
+        last_time_slice: Option<u64>,
+    ) -> result::Result<Verification, Rejection> {
+        self.verify_detailed(
+            secret,
+            code,
+            discrepancy,
+            now_if_zero(time_slice),
+            last_time_slice,
+        )
+    }
+
+    /// Verifies a code at the given time slice, or at the current one if `None` is given.
+    pub(crate) fn verify_detailed<S: ToPreparedSecret + ?Sized>(
+        &self,
+        secret: &S,
+        code: &str,
+        discrepancy: u64,
+        time_slice: Option<u64>,
+        last_time_slice: Option<u64>,
+    ) -> result::Result<Verification, Rejection> {
+        let secret = self.check_code(secret, code)?;
+        let curr_time_slice = self
+            .time_slice(time_slice)
+            .map_err(|_| Rejection::BadConfiguration)?;
+        self.verify_window(
+            &secret,
+            code,
+            curr_time_slice,
+            curr_time_slice,
+            discrepancy,
+            discrepancy,
+            last_time_slice,
+        )
+    }
+
+    /// Checks the configuration, the secret and the format of the code before verification, and
+    /// returns the prepared secret.
+    pub(crate) fn check_code<'a, S: ToPreparedSecret + ?Sized>(
+        &self,
+        secret: &'a S,
+        code: &str,
+    ) -> result::Result<Cow<'a, PreparedSecret>, Rejection> {
+        if self.code_len < CODE_MIN_LEN || self.code_len > CODE_MAX_LEN || self.period == 0 {
+            return Err(Rejection::BadConfiguration);
+        }
+        let secret = secret
+            .to_prepared_secret(self.algorithm)
+            .map_err(|_| Rejection::BadSecret)?;
         if code.len() != self.code_len {
-            return false;
+            return Err(Rejection::WrongLength);
+        }
+        if !code.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(Rejection::NonNumeric);
+        }
+        Ok(secret)
+    }
+
+    /// Searches the `before` time slices before and the `after` time slices after
+    /// `center_time_slice` for the code. Offsets are reported relative to `curr_time_slice`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn verify_window(
+        &self,
+        secret: &PreparedSecret,
+        code: &str,
+        curr_time_slice: u64,
+        center_time_slice: u64,
+        before: u64,
+        after: u64,
+        last_time_slice: Option<u64>,
+    ) -> result::Result<Verification, Rejection> {
+        let start_time = center_time_slice.saturating_sub(before);
+        let end_time = center_time_slice.saturating_add(after);
+        // The whole window is evaluated and codes are compared in constant time, so that the
+        // time taken does not reveal whether or where the code matched.
+        let mut matched = Choice::from(0);
+        let mut reused = Choice::from(0);
+        let mut matched_time_slice = 0;
+        for _time_slice in start_time..=end_time {
+            let is_code = self.constant_time_eq(secret, code, _time_slice);
+            let is_used =
+                Choice::from(last_time_slice.is_some_and(|last| _time_slice <= last) as u8);
+            let accept = is_code & !is_used & !matched;
+            matched_time_slice.conditional_assign(&_time_slice, accept);
+            matched |= accept;
+            reused |= is_code & is_used;
         }
-        let curr_time_slice = if time_slice == 0 {
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                / 30
+        if bool::from(matched) {
+            Ok(Verification {
+                time_slice: matched_time_slice,
+                offset: matched_time_slice.wrapping_sub(curr_time_slice) as i64,
+            })
+        } else if bool::from(reused) {
+            Err(Rejection::Reused)
         } else {
-            time_slice
-        };
-        let start_time = curr_time_slice.saturating_sub(discrepancy);
-        let end_time = curr_time_slice.saturating_add(discrepancy + 1);
-        for _time_slice in start_time..end_time {
-            if let Ok(c) = self.get_code(secret, _time_slice) {
-                if code == c {
-                    return true;
-                }
-            }
+            Err(Rejection::OutsideWindow)
         }
-        false
     }
 
-    /// Get QR-Code URL for image, from google charts. For the height and width, if a value of 0 is
-    /// provided, the default of `200px` is used. Level is the amount of fault tolerance that the
-    /// QR code should accept, see
+    /// Compares the code of the given counter with `code` in constant time.
+    fn constant_time_eq(&self, secret: &PreparedSecret, code: &str, counter: u64) -> Choice {
+        secret
+            .hotp(counter, self.code_len)
+            .as_bytes()
+            .ct_eq(code.as_bytes())
+    }
+
+    /// This function verifies a counter based code (HOTP, RFC 4226). `counter` is the next counter
+    /// value expected from the token, and `look_ahead` the number of further counter values that
+    /// are tried, to resynchronise with tokens whose button was pressed without submitting the
+    /// code. On success, the counter that has to be stored for the next verification is returned,
+    /// which is the matching counter plus one.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::GoogleAuthenticator;
+    ///
+    /// let authenticator = GoogleAuthenticator::new();
+    /// let secret = "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3";
+    /// let code = authenticator.get_hotp_code(secret, 12).unwrap();
+    /// assert_eq!(Some(13), authenticator.verify_hotp_code(secret, &code, 10, 5));
+    /// ```
+    pub fn verify_hotp_code<S: ToPreparedSecret + ?Sized>(
+        &self,
+        secret: &S,
+        code: &str,
+        counter: u64,
+        look_ahead: u64,
+    ) -> Option<u64> {
+        if self.code_len < CODE_MIN_LEN || self.code_len > CODE_MAX_LEN {
+            return None;
+        }
+        if code.len() != self.code_len {
+            return None;
+        }
+        let secret = secret.to_prepared_secret(self.algorithm).ok()?;
+        let end_counter = counter.saturating_add(look_ahead);
+        let mut matched = Choice::from(0);
+        let mut matched_counter = 0;
+        for _counter in counter..=end_counter {
+            let accept = self.constant_time_eq(&secret, code, _counter) & !matched;
+            matched_counter.conditional_assign(&_counter, accept);
+            matched |= accept;
+        }
+        if bool::from(matched) {
+            matched_counter.checked_add(1)
+        } else {
+            None
+        }
+    }
+
+    /// Get a self-contained `data:image/svg+xml;base64,` URL with the QR code, which can be used as
+    /// the `src` of an image. The QR code is rendered locally, so the secret is not sent anywhere.
+    /// For the height and width, if a value of 0 is provided, the default of `200px` is used. Level
+    /// is the amount of fault tolerance that the QR code should accept, see
     /// [this page](https://en.wikipedia.org/wiki/QR_code#Error_correction) for more information.
+    /// An error is returned if the account does not fit in a QR code. Requires the feature flag
+    /// `qrcode`, which is enabled by default.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::{GoogleAuthenticator, ErrorCorrectionLevel};
+    ///
+    /// let authenticator = GoogleAuthenticator::new();
+    /// let url = authenticator
+    ///     .qr_code_url(
+    ///         "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3",
+    ///         "your company name",
+    ///         "hello",
+    ///         0,
+    ///         0,
+    ///         ErrorCorrectionLevel::Medium,
+    ///     )
+    ///     .unwrap();
+    /// assert!(url.starts_with("data:image/svg+xml;base64,"));
+    /// ```
+    #[cfg(any(feature = "qrcode", doc))]
+    pub fn qr_code_url(
+        &self,
+        secret: &str,
+        name: &str,
+        title: &str,
+        width: u32,
+        height: u32,
+        level: ErrorCorrectionLevel,
+    ) -> Result<String> {
+        let scheme = self.create_scheme(name, secret, title, None)?;
+        Self::svg_data_url(&scheme, width, height, level)
+    }
+
+    /// Get a `data:image/svg+xml;base64,` URL with the QR code of a counter based (HOTP) account. The
+    /// `counter` parameter is the initial counter of the token. See `qr_code_url` for the other
+    /// parameters.
     ///
     /// ### Example
     /// ```rust
     /// use google_authenticator::{GoogleAuthenticator, ErrorCorrectionLevel};
     ///
     /// let authenticator = GoogleAuthenticator::new();
-    /// authenticator.qr_code_url(
+    /// authenticator.hotp_qr_code_url(
     ///     "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3",
     ///     "your company name",
     ///     "hello",
     ///     0,
     ///     0,
+    ///     0,
     ///     ErrorCorrectionLevel::Medium,
     /// );
     /// ```
-    pub fn qr_code_url(
+    #[cfg(any(feature = "qrcode", doc))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn hotp_qr_code_url(
         &self,
         secret: &str,
         name: &str,
         title: &str,
+        counter: u64,
         width: u32,
         height: u32,
         level: ErrorCorrectionLevel,
-    ) -> String {
-        let width = if width == 0 { 200 } else { width };
-        let height = if height == 0 { 200 } else { height };
-
-        // Scheme will be a url to the totp we will use. Since it is a query parameter of the final
-        // url, it must be percent encoded. In turn, this means that `name` and `title` must be
-        // percent encoded twice for the final url to work if they contain characters that are not
-        // allowed in urls.
-        let scheme = Self::create_scheme(name, secret, title);
-        let scheme = utf8_percent_encode(&scheme, NON_ALPHANUMERIC);
-        format!(
-            "https://chart.googleapis.com/chart?chs={}x{}&chld={}|0&cht=qr&chl={}",
-            width, height, level, scheme
-        )
+    ) -> Result<String> {
+        let scheme = self.create_scheme(name, secret, title, Some(counter))?;
+        Self::svg_data_url(&scheme, width, height, level)
     }
 
     /// Creates an in-memory SVG file that can be used to perform 2fa with Google Authenticator. The
     /// `height` and `width` parameters are the minimun dimensions of the generated svg. When 0 is
-    /// supplied here, these values default to `200px`.
+    /// supplied here, these values default to `200px`. The modules are black on white, use
+    /// `QrStyle::svg` with `otp_auth_uri` for other colours, rounded modules or a logo.
     ///
     /// ### Example
     /// ```rust
@@ -298,9 +717,144 @@ impl GoogleAuthenticator {
         height: u32,
         level: ErrorCorrectionLevel,
     ) -> Result<String> {
+        let scheme = self.create_scheme(name, secret, title, None)?;
+        Self::svg(&scheme, width, height, level)
+    }
+
+    /// Creates an in-memory SVG file of a counter based (HOTP) account. The `counter` parameter is
+    /// the initial counter of the token. See `qr_code` for the other parameters.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::{GoogleAuthenticator, ErrorCorrectionLevel};
+    ///
+    /// let authenticator = GoogleAuthenticator::new();
+    /// authenticator.hotp_qr_code(
+    ///     "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3",
+    ///     "your company name",
+    ///     "hello",
+    ///     0,
+    ///     0,
+    ///     0,
+    ///     ErrorCorrectionLevel::Medium,
+    /// );
+    /// ```
+    #[cfg(any(feature = "with-qrcode", doc))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn hotp_qr_code(
+        &self,
+        secret: &str,
+        name: &str,
+        title: &str,
+        counter: u64,
+        width: u32,
+        height: u32,
+        level: ErrorCorrectionLevel,
+    ) -> Result<String> {
+        let scheme = self.create_scheme(name, secret, title, Some(counter))?;
+        Self::svg(&scheme, width, height, level)
+    }
+
+    /// Creates an in-memory PNG image that can be used to perform 2fa with Google Authenticator.
+    /// The parameters are the same as for `qr_code`. Use `QrStyle` to change the colours, quiet
+    /// zone or module size. This function is only available with the feature flag `with-qrcode`.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::{GoogleAuthenticator, ErrorCorrectionLevel};
+    ///
+    /// let authenticator = GoogleAuthenticator::new();
+    /// let png = authenticator
+    ///     .qr_code_png(
+    ///         "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3",
+    ///         "your company name",
+    ///         "hello",
+    ///         0,
+    ///         0,
+    ///         ErrorCorrectionLevel::Medium,
+    ///     )
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "with-qrcode")]
+    pub fn qr_code_png(
+        &self,
+        secret: &str,
+        name: &str,
+        title: &str,
+        width: u32,
+        height: u32,
+        level: ErrorCorrectionLevel,
+    ) -> Result<Vec<u8>> {
+        let scheme = self.create_scheme(name, secret, title, None)?;
+        QrStyle::default().png(&scheme, width, height, level)
+    }
+
+    /// Renders the QR code as text that can be printed to a terminal, with two rows of modules per
+    /// line drawn with Unicode half blocks, followed by a line with the `otpauth://` URI. The
+    /// parameters are the same as for `qr_code`, without the size. Use `TerminalQr` for ANSI
+    /// colours, terminals with a dark background, a larger rendering or to leave out the URI.
+    /// This function is only available with the feature flag `with-qrcode`.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::{GoogleAuthenticator, ErrorCorrectionLevel};
+    ///
+    /// let authenticator = GoogleAuthenticator::new();
+    /// let text = authenticator
+    ///     .qr_code_terminal(
+    ///         "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3",
+    ///         "your company name",
+    ///         "hello",
+    ///         ErrorCorrectionLevel::Medium,
+    ///     )
+    ///     .unwrap();
+    /// println!("{}", text);
+    /// ```
+    #[cfg(feature = "with-qrcode")]
+    pub fn qr_code_terminal(
+        &self,
+        secret: &str,
+        name: &str,
+        title: &str,
+        level: ErrorCorrectionLevel,
+    ) -> Result<String> {
+        let scheme = self.create_scheme(name, secret, title, None)?;
+        TerminalQr::default().render(&scheme, level)
+    }
+
+    #[cfg(any(feature = "with-qrcode", doc))]
+    pub(crate) fn svg(
+        scheme: &str,
+        width: u32,
+        height: u32,
+        level: ErrorCorrectionLevel,
+    ) -> Result<String> {
+        Ok(Self::render_svg(scheme, width, height, level)?)
+    }
+
+    #[cfg(any(feature = "qrcode", doc))]
+    pub(crate) fn svg_data_url(
+        scheme: &str,
+        width: u32,
+        height: u32,
+        level: ErrorCorrectionLevel,
+    ) -> Result<String> {
+        let svg = Self::render_svg(scheme, width, height, level)?;
+        Ok(format!(
+            "data:image/svg+xml;base64,{}",
+            STANDARD.encode(svg)
+        ))
+    }
+
+    #[cfg(any(feature = "qrcode", doc))]
+    fn render_svg(
+        scheme: &str,
+        width: u32,
+        height: u32,
+        level: ErrorCorrectionLevel,
+    ) -> result::Result<String, QrError> {
         let width = if width == 0 { 200 } else { width };
         let height = if height == 0 { 200 } else { height };
-        let scheme = Self::create_scheme(name, secret, title);
         let code = QrCode::with_error_correction_level(scheme.as_bytes(), level.into())?;
         Ok(code
             .render()
@@ -310,63 +864,165 @@ impl GoogleAuthenticator {
             .build())
     }
 
-    /// Creates a totp url.
-    fn create_scheme(name: &str, secret: &str, title: &str) -> String {
-        let name = utf8_percent_encode(name, NON_ALPHANUMERIC);
-        let title = utf8_percent_encode(title, NON_ALPHANUMERIC);
-        format!("otpauth://totp/{}?secret={}&issuer={}", name, secret, title)
+    /// Returns the given time slice, or the current one according to the clock if `None` is
+    /// given.
+    pub(crate) fn time_slice(&self, time_slice: Option<u64>) -> Result<u64> {
+        if self.period == 0 {
+            return Err(GAError::Error("bad period. must be at least one second"));
+        }
+        match time_slice {
+            Some(time_slice) => Ok(time_slice),
+            None => Ok(self.clock.now().saturating_sub(self.epoch) / self.period),
+        }
+    }
+
+    /// Creates an `otpauth://` URI builder for a time based account, using the algorithm, code
+    /// length and period of this authenticator. `name` is the account name, and `title` the
+    /// issuer, which is left out when empty. The returned `OtpAuthUri` can be further customised
+    /// before it is turned into a string.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::GoogleAuthenticator;
+    ///
+    /// let authenticator = GoogleAuthenticator::new().with_code_length(8);
+    /// let uri = authenticator
+    ///     .otp_auth_uri("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", "john@example.com", "ACME Co")
+    ///     .with_parameter("image", "https://example.com/logo.png");
+    /// assert_eq!(
+    ///     "otpauth://totp/ACME%20Co:john%40example.com?secret=I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3&issuer=ACME%20Co&algorithm=SHA1&digits=8&period=30&image=https%3A%2F%2Fexample.com%2Flogo.png",
+    ///     uri.to_string(),
+    /// );
+    /// ```
+    pub fn otp_auth_uri(&self, secret: &str, name: &str, title: &str) -> OtpAuthUri {
+        // The Key Uri Format asks for secrets without padding.
+        let secret = match Secret::normalize(secret) {
+            Ok(secret) => secret.trim_end_matches('=').to_string(),
+            Err(_) => secret.to_string(),
+        };
+        let uri = OtpAuthUri::new(&secret, name)
+            .with_algorithm(self.algorithm)
+            .with_digits(self.code_len)
+            .with_period(Duration::from_secs(self.period));
+        if title.is_empty() {
+            uri
+        } else {
+            uri.with_issuer(title)
+        }
+    }
+
+    /// Creates a totp url, or a hotp url if a `counter` is given.
+    #[cfg(any(feature = "qrcode", doc))]
+    fn create_scheme(
+        &self,
+        name: &str,
+        secret: &str,
+        title: &str,
+        counter: Option<u64>,
+    ) -> Result<String> {
+        let uri = self.otp_auth_uri(secret, name, title);
+        let uri = match counter {
+            Some(counter) => uri.with_counter(counter),
+            None => uri,
+        };
+        uri.validate()?;
+        Ok(uri.to_string())
+    }
+
+    /// Decodes a secret as it may be entered by users: in any case, grouped with spaces or
+    /// dashes, and with or without padding. The decoded secret must be 10 to 80 bytes long.
+    pub(crate) fn base32_decode(secret: &str) -> Result<Vec<u8>> {
+        let mut normalized: String = secret
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect();
+        let key = Self::base32_decode_normalized(normalized.trim_end_matches('='));
+        normalized.zeroize();
+        key
     }
 
-    fn base32_decode(secret: &str) -> Result<Vec<u8>> {
-        match base32::decode(base32::Alphabet::RFC4648 { padding: true }, secret) {
-            Some(_decode_str) => Ok(_decode_str),
-            _ => Err(GAError::Error("secret must be base32 decodeable.")),
+    fn base32_decode_normalized(normalized: &str) -> Result<Vec<u8>> {
+        // Only these numbers of trailing characters encode whole bytes.
+        if matches!(normalized.len() % 8, 1 | 3 | 6)
+            || !normalized
+                .bytes()
+                .all(|b| b.is_ascii_alphabetic() || (b'2'..=b'7').contains(&b))
+        {
+            return Err(GAError::Error("secret must be base32 decodeable."));
         }
+        let mut key = match base32::decode(base32::Alphabet::RFC4648 { padding: false }, normalized)
+        {
+            Some(_decode_str) => _decode_str,
+            _ => return Err(GAError::Error("secret must be base32 decodeable.")),
+        };
+        if key.len() < SECRET_MIN_BYTES || key.len() > SECRET_MAX_BYTES {
+            key.zeroize();
+            return Err(GAError::Error(
+                "bad secret length. must be between 80 and 640 bits, recommend 160",
+            ));
+        }
+        Ok(key)
     }
 }
 
+/// The time slice of functions where 0 stands for the current time, which is `None`.
+pub(crate) fn now_if_zero(time_slice: u64) -> Option<u64> {
+    Some(time_slice).filter(|&time_slice| time_slice != 0)
+}
+
 /// Represents any of the reasons why using 2fa with Google Authenenticator can fail.
 #[derive(Debug)]
 pub enum GAError {
     /// An error in the logic of the QR code. Contains a static string with the error message.
     Error(&'static str),
     /// An error related to the QR code. This variant is only available with the feature flag
-    /// `with-qrcode`.
-    #[cfg(any(feature = "with-qrcode",doc))]
+    /// `qrcode`, which is enabled by default.
+    #[cfg(any(feature = "qrcode", doc))]
     QrError(QrError),
+    /// An error while reading or writing a file.
+    IoError(io::Error),
 }
 
 impl error::Error for GAError {
     fn description(&self) -> &str {
         match *self {
             GAError::Error(description) => description,
-            #[cfg(any(feature = "with-qrcode",doc))]
+            #[cfg(any(feature = "qrcode", doc))]
             GAError::QrError(ref _err) => "",
+            GAError::IoError(ref _err) => "",
         }
     }
 
     fn cause(&self) -> Option<&dyn error::Error> {
         match *self {
-            #[cfg(any(feature = "with-qrcode", doc))]
+            #[cfg(any(feature = "qrcode", doc))]
             GAError::QrError(ref _err) => None,
+            GAError::IoError(ref err) => Some(err),
             GAError::Error(_) => None,
         }
     }
 }
 
-#[cfg(any(feature = "with-qrcode", doc))]
+#[cfg(any(feature = "qrcode", doc))]
 impl From<QrError> for GAError {
     fn from(err: QrError) -> GAError {
         GAError::QrError(err)
     }
 }
 
+impl From<io::Error> for GAError {
+    fn from(err: io::Error) -> GAError {
+        GAError::IoError(err)
+    }
+}
+
 impl fmt::Display for GAError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             GAError::Error(desc) => f.write_str(desc),
-            #[cfg(any(feature = "with-qrcode",doc))]
+            #[cfg(any(feature = "qrcode", doc))]
             GAError::QrError(ref err) => fmt::Display::fmt(err, f),
+            GAError::IoError(ref err) => fmt::Display::fmt(err, f),
         }
     }
 }
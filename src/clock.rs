@@ -0,0 +1,132 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of the current time, used by `GoogleAuthenticator` whenever a time slice of 0 is
+/// passed. Replacing the clock makes it possible to freeze time in tests, or to correct servers
+/// whose clock is known to be wrong.
+pub trait Clock {
+    /// The current time, as the number of seconds since the unix epoch.
+    fn now(&self) -> u64;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Box<C> {
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
+
+/// The clock of the system, which is the default clock of `GoogleAuthenticator`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+}
+
+/// A clock that always returns the same time, until it is changed with `set` or `advance`. Share
+/// it in an `Arc` to change the time of a `GoogleAuthenticator` it was given to.
+///
+/// ### Example
+/// ```rust
+/// use google_authenticator::{FixedClock, GoogleAuthenticator};
+/// use std::sync::Arc;
+///
+/// let clock = Arc::new(FixedClock::new(59));
+/// let auth = GoogleAuthenticator::new().with_clock(clock.clone());
+/// let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+/// assert_eq!("287082", auth.get_code(secret, 0).unwrap());
+/// clock.advance(30);
+/// assert_eq!("359152", auth.get_code(secret, 0).unwrap());
+/// ```
+#[derive(Debug, Default)]
+pub struct FixedClock {
+    time: AtomicU64,
+}
+
+impl FixedClock {
+    /// Create a new `FixedClock` at the given number of seconds since the unix epoch.
+    pub fn new(time: u64) -> Self {
+        FixedClock {
+            time: AtomicU64::new(time),
+        }
+    }
+
+    /// Set the time to the given number of seconds since the unix epoch.
+    pub fn set(&self, time: u64) {
+        self.time.store(time, Ordering::SeqCst);
+    }
+
+    /// Move the time forward by the given number of seconds.
+    pub fn advance(&self, seconds: u64) {
+        self.time.fetch_add(seconds, Ordering::SeqCst);
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.time.load(Ordering::SeqCst)
+    }
+}
+
+/// A clock that adds a fixed number of seconds to another clock, which may be negative. Use it
+/// for servers whose clock is known to be skewed.
+///
+/// ### Example
+/// ```rust
+/// use google_authenticator::{GoogleAuthenticator, OffsetClock, SystemClock};
+///
+/// // The clock of this server is known to be 90 seconds behind.
+/// let auth = GoogleAuthenticator::new().with_clock(OffsetClock::new(SystemClock, 90));
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OffsetClock<C> {
+    clock: C,
+    offset: i64,
+}
+
+impl<C: Clock> OffsetClock<C> {
+    /// Create a new `OffsetClock` that adds `offset` seconds to `clock`.
+    pub fn new(clock: C, offset: i64) -> Self {
+        OffsetClock { clock, offset }
+    }
+}
+
+impl<C: Clock> Clock for OffsetClock<C> {
+    fn now(&self) -> u64 {
+        self.clock.now().saturating_add_signed(self.offset)
+    }
+}
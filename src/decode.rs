@@ -0,0 +1,166 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{GAError, OtpAuthUri, Result};
+use resvg::{tiny_skia, usvg};
+use std::io::Cursor;
+
+/// The smallest width or height that SVG images are rasterised to, so that each module of the
+/// largest QR codes spans a few pixels.
+/// cbindgen:ignore
+const SVG_MIN_SIZE: f32 = 1024.0;
+
+/// The largest number of pixels of a decoded image, which bounds the memory used by untrusted
+/// images. It fits screenshots of 5K displays.
+/// cbindgen:ignore
+const IMAGE_MAX_PIXELS: u64 = 1 << 24;
+
+/// Decodes the QR code in a PNG, JPEG or SVG image and returns its text, for example an
+/// `otpauth://` URI from a screenshot of an enrollment page. QR codes with light modules on a
+/// dark background are decoded as well. Images with more than 16777216 pixels, or SVG images
+/// that are that large when rasterised, are rejected before they are decoded. This function is
+/// only available with the feature flag `qr-decode`.
+///
+/// ### Example
+/// ```rust,no_run
+/// use google_authenticator::decode_qr_code;
+///
+/// let screenshot = std::fs::read("screenshot.png").unwrap();
+/// let text = decode_qr_code(&screenshot).unwrap();
+/// assert!(text.starts_with("otpauth://"));
+/// ```
+pub fn decode_qr_code(image: &[u8]) -> Result<String> {
+    let (width, height, luma) = if is_svg(image) {
+        rasterise_svg(image)?
+    } else {
+        let reader = || {
+            image::ImageReader::new(Cursor::new(image))
+                .with_guessed_format()
+                .map_err(GAError::from)
+        };
+        let (width, height) = reader()?
+            .into_dimensions()
+            .map_err(|_| GAError::Error("can't decode image. must be png, jpeg or svg"))?;
+        check_size(width as u64, height as u64)?;
+        let image = reader()?
+            .decode()
+            .map_err(|_| GAError::Error("can't decode image. must be png, jpeg or svg"))?
+            .to_rgba8();
+        let luma = image
+            .pixels()
+            .map(|pixel| {
+                let [r, g, b, a] = pixel.0;
+                let luma = (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000;
+                // Transparent pixels are shown on white.
+                ((luma * a as u32 + 255 * (255 - a as u32)) / 255) as u8
+            })
+            .collect();
+        (image.width() as usize, image.height() as usize, luma)
+    };
+    decode_luma(width, height, &luma, false)
+        .or_else(|| decode_luma(width, height, &luma, true))
+        .ok_or(GAError::Error("no qr code found in image"))
+}
+
+impl OtpAuthUri {
+    /// Decodes the QR code in a PNG, JPEG or SVG image and parses the `otpauth://` URI it
+    /// contains. See `decode_qr_code` for the supported images.
+    ///
+    /// ### Example
+    /// ```rust,no_run
+    /// use google_authenticator::OtpAuthUri;
+    ///
+    /// let screenshot = std::fs::read("screenshot.jpg").unwrap();
+    /// let uri = OtpAuthUri::from_qr_code(&screenshot).unwrap();
+    /// println!("{} from {:?}", uri.account, uri.issuer);
+    /// ```
+    pub fn from_qr_code(image: &[u8]) -> Result<OtpAuthUri> {
+        decode_qr_code(image)?.parse()
+    }
+}
+
+/// Rejects images with more than `IMAGE_MAX_PIXELS` pixels before they are decoded.
+fn check_size(width: u64, height: u64) -> Result<()> {
+    if width.saturating_mul(height) > IMAGE_MAX_PIXELS {
+        return Err(GAError::Error(
+            "image too large. must have at most 16777216 pixels",
+        ));
+    }
+    Ok(())
+}
+
+/// Decodes the first readable QR code in a greyscale image, optionally with inverted colours.
+fn decode_luma(width: usize, height: usize, luma: &[u8], inverted: bool) -> Option<String> {
+    let mut image = rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| {
+        let luma = luma[y * width + x];
+        if inverted {
+            255 - luma
+        } else {
+            luma
+        }
+    });
+    image
+        .detect_grids()
+        .into_iter()
+        .find_map(|grid| grid.decode().ok().map(|(_, text)| text))
+}
+
+fn is_svg(image: &[u8]) -> bool {
+    let start = image
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(image.len());
+    image[start..].starts_with(b"<")
+}
+
+/// Renders an SVG image on white and returns its size and greyscale pixels.
+fn rasterise_svg(svg: &[u8]) -> Result<(usize, usize, Vec<u8>)> {
+    let tree = usvg::Tree::from_data(svg, &usvg::Options::default())
+        .map_err(|_| GAError::Error("can't decode svg image"))?;
+    let size = tree.size();
+    let scale = (SVG_MIN_SIZE / size.width().min(size.height())).max(1.0);
+    let width = (size.width() * scale).ceil();
+    let height = (size.height() * scale).ceil();
+    check_size(width as u64, height as u64)?;
+    let (width, height) = (width as u32, height as u32);
+    let mut pixmap =
+        tiny_skia::Pixmap::new(width, height).ok_or(GAError::Error("can't decode svg image"))?;
+    pixmap.fill(tiny_skia::Color::WHITE);
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    let luma = pixmap
+        .pixels()
+        .iter()
+        .map(|pixel| {
+            let (r, g, b) = (
+                pixel.red() as u32,
+                pixel.green() as u32,
+                pixel.blue() as u32,
+            );
+            ((299 * r + 587 * g + 114 * b) / 1000) as u8
+        })
+        .collect();
+    Ok((width as usize, height as usize, luma))
+}
@@ -0,0 +1,159 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::authenticator::now_if_zero;
+use crate::{GoogleAuthenticator, Rejection, ToPreparedSecret, Verification};
+use std::result;
+
+/// Tracks the clock drift of the device of a single account. The learned offset is used to
+/// center the verification window, so that tokens whose clock slowly drifts away keep working
+/// without widening the window for everyone. The offset should be stored with the account, and
+/// restored with `ClockDrift::new`.
+///
+/// ### Example
+/// ```rust
+/// use google_authenticator::{ClockDrift, GoogleAuthenticator};
+///
+/// let authenticator = GoogleAuthenticator::new();
+/// let secret = "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3";
+/// let time_slice = 1523610659 / 30;
+///
+/// // The clock of the token is 10 periods ahead, which is resynchronised once using two
+/// // consecutive codes.
+/// let first = authenticator.get_code(secret, time_slice + 9).unwrap();
+/// let second = authenticator.get_code(secret, time_slice + 10).unwrap();
+/// let mut drift = ClockDrift::default();
+/// drift
+///     .resync(&authenticator, secret, &first, &second, 20, time_slice)
+///     .unwrap();
+/// assert_eq!(10, drift.offset());
+///
+/// // From then on, codes are verified in a small window around the drifted clock.
+/// let code = authenticator.get_code(secret, time_slice + 11).unwrap();
+/// assert!(drift
+///     .verify(&authenticator, secret, &code, 1, time_slice + 1)
+///     .is_ok());
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClockDrift {
+    offset: i64,
+}
+
+impl ClockDrift {
+    /// Create a new `ClockDrift` with a previously learned offset, in periods.
+    pub fn new(offset: i64) -> Self {
+        ClockDrift { offset }
+    }
+
+    /// The learned offset in periods. A positive offset means that the clock of the device is
+    /// ahead.
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    /// This function verifies a code like `GoogleAuthenticator::verify_code_detailed`, but the
+    /// window of `discrepancy` time slices is centered on the current time slice shifted by the
+    /// learned offset. On success, the offset is updated to the offset of the matched time slice.
+    pub fn verify<S: ToPreparedSecret + ?Sized>(
+        &mut self,
+        authenticator: &GoogleAuthenticator,
+        secret: &S,
+        code: &str,
+        discrepancy: u64,
+        time_slice: u64,
+    ) -> result::Result<Verification, Rejection> {
+        let secret = authenticator.check_code(secret, code)?;
+        let curr_time_slice = authenticator
+            .time_slice(now_if_zero(time_slice))
+            .map_err(|_| Rejection::BadConfiguration)?;
+        let verification = authenticator.verify_window(
+            &secret,
+            code,
+            curr_time_slice,
+            curr_time_slice.saturating_add_signed(self.offset),
+            discrepancy,
+            discrepancy,
+            None,
+        )?;
+        self.offset = verification.offset;
+        Ok(verification)
+    }
+
+    /// Resynchronises with a device whose clock drifted further than the verification window.
+    /// `first_code` and `second_code` are two consecutive codes of the device, which are searched
+    /// for in the `max_skew` time slices around the current one. On success, the offset is set to
+    /// the offset of the time slice of `second_code`, which is returned. As it is unlikely to guess
+    /// two consecutive codes, `max_skew` can be much larger than the discrepancy used by
+    /// `verify`.
+    pub fn resync<S: ToPreparedSecret + ?Sized>(
+        &mut self,
+        authenticator: &GoogleAuthenticator,
+        secret: &S,
+        first_code: &str,
+        second_code: &str,
+        max_skew: u64,
+        time_slice: u64,
+    ) -> result::Result<Verification, Rejection> {
+        let secret = authenticator.check_code(secret, first_code)?;
+        authenticator.check_code(&*secret, second_code)?;
+        let curr_time_slice = authenticator
+            .time_slice(now_if_zero(time_slice))
+            .map_err(|_| Rejection::BadConfiguration)?;
+        let start_time = curr_time_slice.saturating_sub(max_skew);
+        let end_time = curr_time_slice.saturating_add(max_skew);
+        for _time_slice in start_time..=end_time {
+            let Some(next_time_slice) = _time_slice.checked_add(1) else {
+                break;
+            };
+            if authenticator
+                .verify_window(
+                    &secret,
+                    first_code,
+                    curr_time_slice,
+                    _time_slice,
+                    0,
+                    0,
+                    None,
+                )
+                .is_ok()
+                && authenticator
+                    .verify_window(
+                        &secret,
+                        second_code,
+                        curr_time_slice,
+                        next_time_slice,
+                        0,
+                        0,
+                        None,
+                    )
+                    .is_ok()
+            {
+                self.offset = next_time_slice.wrapping_sub(curr_time_slice) as i64;
+                return Ok(Verification {
+                    time_slice: next_time_slice,
+                    offset: self.offset,
+                });
+            }
+        }
+        Err(Rejection::OutsideWindow)
+    }
+}
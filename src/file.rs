@@ -0,0 +1,428 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{Algorithm, FixedClock, GAError, GoogleAuthenticator, PreparedSecret, Result};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, Rng, RngCore};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use std::{fmt, io};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+/// cbindgen:ignore
+const SCRATCH_CODE_LEN: usize = 8;
+/// cbindgen:ignore
+const DEFAULT_WINDOW_SIZE: u64 = 3;
+/// cbindgen:ignore
+const DEFAULT_STEP_SIZE: u64 = 30;
+
+/// The `~/.google_authenticator` file of libpam-google-authenticator. It holds the secret of a
+/// user on the first line, followed by option lines starting with `"` and the 8-digit scratch
+/// codes. Options that this crate does not know, like `" TIME_SKEW`, are kept as they are.
+///
+/// `verify` honours the options like the PAM module does, and `verify_file` also writes the
+/// updated state back to the file atomically.
+///
+/// ### Example
+/// ```rust
+/// use google_authenticator::{AuthenticatorFile, GoogleAuthenticator};
+///
+/// let mut file: AuthenticatorFile = "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3\n\
+///     \" RATE_LIMIT 3 30\n\
+///     \" WINDOW_SIZE 17\n\
+///     \" DISALLOW_REUSE\n\
+///     \" TOTP_AUTH\n\
+///     12345678\n"
+///     .parse()
+///     .unwrap();
+///
+/// let time = 1523610659;
+/// let code = file.authenticator().get_code(&file.secret, time / 30).unwrap();
+/// assert!(file.verify(&code, time).unwrap());
+/// assert!(!file.verify(&code, time).unwrap());
+/// assert!(file.verify("12345678", time + 30).unwrap());
+/// assert!(file.scratch_codes.is_empty());
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct AuthenticatorFile {
+    /// The base32 encoded secret.
+    pub secret: String,
+    /// `" RATE_LIMIT`, the number of login attempts allowed per interval.
+    pub rate_limit: Option<RateLimit>,
+    /// `" WINDOW_SIZE`, the number of codes that are accepted, 3 if not set.
+    pub window_size: Option<u64>,
+    /// `" DISALLOW_REUSE`, the time slices of the codes that were already accepted. If set,
+    /// these codes are rejected.
+    pub disallow_reuse: Option<Vec<u64>>,
+    /// `" TOTP_AUTH`, whether time based codes are used.
+    pub totp_auth: bool,
+    /// `" HOTP_COUNTER`, the counter of counter based codes. If set, counter based codes are
+    /// used instead of time based codes.
+    pub hotp_counter: Option<u64>,
+    /// `" STEP_SIZE`, the period of time based codes in seconds, 30 if not set.
+    pub step_size: Option<u64>,
+    /// Option lines that are not known, without the leading `"`.
+    pub other_options: Vec<String>,
+    /// The unused 8-digit scratch codes.
+    pub scratch_codes: Vec<String>,
+}
+
+/// The `" RATE_LIMIT` option of an `AuthenticatorFile`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    /// The number of login attempts that are allowed per interval.
+    pub attempts: usize,
+    /// The interval in seconds.
+    pub interval: u64,
+    /// The unix times of the recent login attempts.
+    pub timestamps: Vec<u64>,
+}
+
+impl AuthenticatorFile {
+    /// Create a new file for time based codes with the given base32 secret, and no other
+    /// options.
+    pub fn new(secret: &str) -> Self {
+        AuthenticatorFile {
+            secret: secret.to_string(),
+            rate_limit: None,
+            window_size: None,
+            disallow_reuse: None,
+            totp_auth: true,
+            hotp_counter: None,
+            step_size: None,
+            other_options: Vec::new(),
+            scratch_codes: Vec::new(),
+        }
+    }
+
+    /// Reads and parses the file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Writes the file to `path` atomically: it is written to a temporary file next to it, named
+    /// like `path` followed by `~`, which then replaces the file. The permissions and, on unix,
+    /// the owner of an existing file are kept, new files are only readable and writable by their
+    /// owner. Symbolic links at the temporary path are not followed, so it is safe to save files
+    /// in directories of other users as root.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push("~");
+        let temp_path = PathBuf::from(temp_path);
+        let metadata = fs::symlink_metadata(path).ok();
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+        }
+        // A temporary file that already exists belongs to a concurrent update, and is left alone.
+        let mut file = options.open(&temp_path)?;
+        let result = (|| -> io::Result<()> {
+            file.write_all(self.to_string().as_bytes())?;
+            if let Some(metadata) = metadata {
+                // Files of users are updated by PAM modules that run as root. The owner and
+                // permissions are changed on the open file, as the path could be replaced.
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::MetadataExt;
+                    let temp_metadata = file.metadata()?;
+                    if (temp_metadata.uid(), temp_metadata.gid())
+                        != (metadata.uid(), metadata.gid())
+                    {
+                        std::os::unix::fs::fchown(
+                            &file,
+                            Some(metadata.uid()),
+                            Some(metadata.gid()),
+                        )?;
+                    }
+                }
+                file.set_permissions(metadata.permissions())?;
+            }
+            file.sync_all()?;
+            fs::rename(&temp_path, path)
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        Ok(result?)
+    }
+
+    /// Loads the file at `path`, verifies `code` at the unix time `time` like `verify`, and saves
+    /// the file if its state changed, even if the code was rejected.
+    pub fn verify_file<P: AsRef<Path>>(path: P, code: &str, time: u64) -> Result<bool> {
+        let path = path.as_ref();
+        let mut file = Self::load(path)?;
+        let original = file.clone();
+        let verified = file.verify(code, time)?;
+        if file != original {
+            file.save(path)?;
+        }
+        Ok(verified)
+    }
+
+    /// A `GoogleAuthenticator` configured with the step size of the file.
+    pub fn authenticator(&self) -> GoogleAuthenticator {
+        GoogleAuthenticator::new().with_period(Duration::from_secs(
+            self.step_size.unwrap_or(DEFAULT_STEP_SIZE),
+        ))
+    }
+
+    /// Generates `count` new scratch codes with the random number generator of the operating
+    /// system, and adds them to the file.
+    pub fn generate_scratch_codes(&mut self, count: usize) {
+        self.generate_scratch_codes_with_rng(&mut OsRng, count)
+    }
+
+    /// Generates `count` new scratch codes with a cryptographically secure random number
+    /// generator, and adds them to the file.
+    pub fn generate_scratch_codes_with_rng<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        count: usize,
+    ) {
+        for _ in 0..count {
+            let code: u32 = rng.gen_range(10_000_000..100_000_000);
+            self.scratch_codes.push(code.to_string());
+        }
+    }
+
+    /// Verifies a code entered at the unix time `time`, and updates the state like
+    /// libpam-google-authenticator does:
+    ///
+    /// - with `RATE_LIMIT`, the attempt is recorded and rejected if there were too many,
+    /// - an 8-digit code is checked against the scratch codes, and a matching one is removed,
+    /// - with `HOTP_COUNTER`, the next `WINDOW_SIZE` counters are checked, and the counter is
+    ///   moved past the matching one, or by one if the code was rejected,
+    /// - with `TOTP_AUTH`, `WINDOW_SIZE` time slices around `time` are checked, and with
+    ///   `DISALLOW_REUSE`, codes of time slices that were accepted before are rejected.
+    ///
+    /// An error is returned if the secret can not be decoded, or neither `TOTP_AUTH` nor
+    /// `HOTP_COUNTER` is set.
+    pub fn verify(&mut self, code: &str, time: u64) -> Result<bool> {
+        let code = code.trim();
+        if let Some(rate_limit) = &mut self.rate_limit {
+            if !rate_limit.attempt(time) {
+                return Ok(false);
+            }
+        }
+        if code.len() == SCRATCH_CODE_LEN {
+            return Ok(self.redeem_scratch_code(code));
+        }
+        let secret = PreparedSecret::new(&self.secret, Algorithm::Sha1)?;
+        let window_size = self.window_size.unwrap_or(DEFAULT_WINDOW_SIZE).max(1);
+        let authenticator = self.authenticator();
+        if let Some(counter) = self.hotp_counter {
+            let next_counter =
+                authenticator.verify_hotp_code(&secret, code, counter, window_size - 1);
+            self.hotp_counter = Some(next_counter.unwrap_or(counter.saturating_add(1)));
+            return Ok(next_counter.is_some());
+        }
+        if !self.totp_auth {
+            return Err(GAError::Error(
+                "the file must set either TOTP_AUTH or HOTP_COUNTER",
+            ));
+        }
+        let authenticator = authenticator.with_clock(FixedClock::new(time));
+        let curr_time_slice = authenticator.time_slice(None)?;
+        let Ok(secret) = authenticator.check_code(&secret, code) else {
+            return Ok(false);
+        };
+        // Like libpam, an even window accepts one more time slice after the current one than
+        // before it.
+        let Ok(verification) = authenticator.verify_window(
+            &secret,
+            code,
+            curr_time_slice,
+            curr_time_slice,
+            (window_size - 1) / 2,
+            window_size / 2,
+            None,
+        ) else {
+            return Ok(false);
+        };
+        if let Some(used) = &mut self.disallow_reuse {
+            if used.contains(&verification.time_slice) {
+                return Ok(false);
+            }
+            used.retain(|t| t.abs_diff(curr_time_slice) <= window_size);
+            used.push(verification.time_slice);
+        }
+        Ok(true)
+    }
+
+    fn redeem_scratch_code(&mut self, code: &str) -> bool {
+        let mut matched = Choice::from(0);
+        let mut index = 0;
+        for (i, scratch_code) in self.scratch_codes.iter().enumerate() {
+            let accept = scratch_code.as_bytes().ct_eq(code.as_bytes()) & !matched;
+            index.conditional_assign(&(i as u64), accept);
+            matched |= accept;
+        }
+        if bool::from(matched) {
+            self.scratch_codes.remove(index as usize);
+        }
+        matched.into()
+    }
+}
+
+impl RateLimit {
+    /// Records a login attempt at the unix time `time`. Returns `false` if there were more than
+    /// `attempts` attempts in the last `interval` seconds.
+    pub fn attempt(&mut self, time: u64) -> bool {
+        self.timestamps
+            .retain(|t| *t <= time && time - *t < self.interval);
+        self.timestamps.push(time);
+        if self.timestamps.len() > self.attempts {
+            let excess = self.timestamps.len() - self.attempts;
+            self.timestamps.drain(..excess);
+            return false;
+        }
+        true
+    }
+}
+
+impl FromStr for AuthenticatorFile {
+    type Err = GAError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        let secret = lines.next().unwrap_or_default().trim();
+        GoogleAuthenticator::base32_decode(secret)?;
+        let mut file = AuthenticatorFile::new(secret);
+        file.totp_auth = false;
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some(option) = line.strip_prefix('"') else {
+                if line.len() != SCRATCH_CODE_LEN || !line.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(GAError::Error("bad scratch code. must be 8 digits"));
+                }
+                file.scratch_codes.push(line.to_string());
+                continue;
+            };
+            let mut words = option.split_whitespace();
+            let name = words.next().unwrap_or_default();
+            let values = words
+                .map(u64::from_str)
+                .collect::<std::result::Result<Vec<_>, _>>();
+            match (name, values) {
+                ("RATE_LIMIT", Ok(values)) if values.len() >= 2 && values[1] > 0 => {
+                    file.rate_limit = Some(RateLimit {
+                        attempts: values[0] as usize,
+                        interval: values[1],
+                        timestamps: values[2..].to_vec(),
+                    });
+                }
+                ("WINDOW_SIZE", Ok(values)) if values.len() == 1 && values[0] > 0 => {
+                    file.window_size = Some(values[0]);
+                }
+                ("DISALLOW_REUSE", Ok(values)) => file.disallow_reuse = Some(values),
+                ("TOTP_AUTH", Ok(values)) if values.is_empty() => file.totp_auth = true,
+                ("HOTP_COUNTER", Ok(values)) if values.len() == 1 => {
+                    file.hotp_counter = Some(values[0]);
+                }
+                ("STEP_SIZE", Ok(values)) if values.len() == 1 && values[0] > 0 => {
+                    file.step_size = Some(values[0]);
+                }
+                (
+                    "RATE_LIMIT" | "WINDOW_SIZE" | "DISALLOW_REUSE" | "TOTP_AUTH" | "HOTP_COUNTER"
+                    | "STEP_SIZE",
+                    _,
+                ) => return Err(GAError::Error("bad option value")),
+                _ => file.other_options.push(option.trim().to_string()),
+            }
+        }
+        Ok(file)
+    }
+}
+
+/// Shows all fields except the secret and the scratch codes, like `Secret`.
+impl fmt::Debug for AuthenticatorFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AuthenticatorFile")
+            .field("secret", &format_args!("***"))
+            .field("rate_limit", &self.rate_limit)
+            .field("window_size", &self.window_size)
+            .field("disallow_reuse", &self.disallow_reuse)
+            .field("totp_auth", &self.totp_auth)
+            .field("hotp_counter", &self.hotp_counter)
+            .field("step_size", &self.step_size)
+            .field("other_options", &self.other_options)
+            .field(
+                "scratch_codes",
+                &vec![format_args!("***"); self.scratch_codes.len()],
+            )
+            .finish()
+    }
+}
+
+impl fmt::Display for AuthenticatorFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.secret)?;
+        if let Some(rate_limit) = &self.rate_limit {
+            write!(
+                f,
+                "\" RATE_LIMIT {} {}",
+                rate_limit.attempts, rate_limit.interval
+            )?;
+            for timestamp in &rate_limit.timestamps {
+                write!(f, " {}", timestamp)?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(window_size) = self.window_size {
+            writeln!(f, "\" WINDOW_SIZE {}", window_size)?;
+        }
+        if let Some(used) = &self.disallow_reuse {
+            write!(f, "\" DISALLOW_REUSE")?;
+            for time_slice in used {
+                write!(f, " {}", time_slice)?;
+            }
+            writeln!(f)?;
+        }
+        if self.totp_auth {
+            writeln!(f, "\" TOTP_AUTH")?;
+        }
+        if let Some(counter) = self.hotp_counter {
+            writeln!(f, "\" HOTP_COUNTER {}", counter)?;
+        }
+        if let Some(step_size) = self.step_size {
+            writeln!(f, "\" STEP_SIZE {}", step_size)?;
+        }
+        for option in &self.other_options {
+            writeln!(f, "\" {}", option)?;
+        }
+        for code in &self.scratch_codes {
+            writeln!(f, "{}", code)?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 #![deny(missing_docs)]
-#![cfg_attr(not(feature = "clib"), deny(unsafe_code))]
+#![cfg_attr(not(any(feature = "clib", feature = "pam")), deny(unsafe_code))]
 // Copyright 2012-2013 The Rust Project Developers. See the COPYRIGHT
 // file at the top-level directory of this distribution and at
 // http://rust-lang.org/COPYRIGHT.
@@ -29,8 +29,34 @@
 //! ```
 
 mod authenticator;
+mod clock;
+#[cfg(feature = "qr-decode")]
+mod decode;
+mod drift;
+mod file;
+/// cbindgen:ignore
+#[cfg(feature = "pam")]
+mod pam;
+#[cfg(feature = "with-qrcode")]
+mod qr;
+mod recovery;
+mod secret;
+mod uri;
+mod verification;
 
 pub use authenticator::*;
+pub use clock::*;
+#[cfg(feature = "qr-decode")]
+pub use decode::*;
+pub use drift::*;
+pub use file::*;
+#[cfg(feature = "with-qrcode")]
+pub use qr::*;
+pub use recovery::*;
+pub use secret::*;
+pub use uri::*;
+pub use verification::*;
+
 #[cfg(feature = "clib")]
 use std::ffi::{CStr, CString};
 #[cfg(feature = "clib")]
@@ -85,6 +111,7 @@ macro_rules! verify_code {
 /// `GoogleAuthenticator::qr_code_url`, by providing a default of 200 to the `width` parameter, 200
 /// to the `height` parameter, and `ErrorCorrectionLevel::Medium` to the `level` parameter.
 #[macro_export]
+#[cfg(any(feature = "qrcode", doc))]
 macro_rules! qr_code_url {
     ($secret: expr, $name: expr, $title: expr, $width: expr, $height: expr, $level: expr) => {
         GA_AUTH.qr_code_url($secret, $name, $title, $width, $height, $level)
@@ -105,7 +132,7 @@ macro_rules! qr_code_url {
 /// `GoogleAuthenticator::qr_code`, by providing a default of 200 to the `width` parameter, 200
 /// to the `height` parameter, and `ErrorCorrectionLevel::Medium` to the `level` parameter.
 #[macro_export]
-#[cfg(any(feature="with-qrcode",doc))]
+#[cfg(any(feature = "with-qrcode", doc))]
 macro_rules! qr_code {
     ($secret: expr, $name: expr, $title: expr, $width: expr, $height: expr, $level: expr) => {
         GA_AUTH.qr_code($secret, $name, $title, $width, $height, $level)
@@ -132,6 +159,7 @@ pub extern "C" fn create_secret(len: u8) -> *const c_char {
         .into_raw()
 }
 
+/// # Safety
 /// A function that can be used for convenient access to the function
 /// `qr_code`, by providing a default of 200 to the `width` parameter, 200
 /// to the `height` parameter, and `ErrorCorrectionLevel::Medium` to the `level` parameter.
@@ -165,7 +193,7 @@ pub unsafe extern "C" fn qr_code(
 /// A function that can be used for convenient access to the function
 /// `qr_code_url`, by providing a default of 200 to the `width` parameter, 200
 /// to the `height` parameter, and `ErrorCorrectionLevel::Medium` to the `level` parameter.
-#[cfg(feature = "clib")]
+#[cfg(all(feature = "qrcode", feature = "clib"))]
 #[no_mangle]
 pub unsafe extern "C" fn qr_code_url(
     secret: *const c_char,
@@ -175,15 +203,19 @@ pub unsafe extern "C" fn qr_code_url(
     height: u32,
     level: crate::ErrorCorrectionLevel,
 ) -> *const c_char {
-    CString::new(GA_AUTH.qr_code_url(
-        unsafe { CStr::from_ptr(secret) }.to_str().unwrap(),
-        unsafe { CStr::from_ptr(name) }.to_str().unwrap(),
-        unsafe { CStr::from_ptr(title) }.to_str().unwrap(),
-        witdh,
-        height,
-        level,
-    ))
-    .expect("can't get qrcode url now.")
+    CString::new(
+        GA_AUTH
+            .qr_code_url(
+                unsafe { CStr::from_ptr(secret) }.to_str().unwrap(),
+                unsafe { CStr::from_ptr(name) }.to_str().unwrap(),
+                unsafe { CStr::from_ptr(title) }.to_str().unwrap(),
+                witdh,
+                height,
+                level,
+            )
+            .expect("can't get qrcode url now."),
+    )
+    .unwrap()
     .into_raw()
 }
 
@@ -227,6 +259,113 @@ pub unsafe extern "C" fn verify_code(
     )
 }
 
+/// # Safety
+/// A function that can be used for convenient access to the function
+/// `get_code`, using the hash function given in the `algorithm` parameter.
+#[cfg(feature = "clib")]
+#[no_mangle]
+pub unsafe extern "C" fn get_code_with_algorithm(
+    secret: *const c_char,
+    time_slice: u64,
+    algorithm: crate::Algorithm,
+) -> *const c_char {
+    CString::new(
+        GoogleAuthenticator::new()
+            .with_algorithm(algorithm)
+            .get_code(
+                unsafe { CStr::from_ptr(secret) }.to_str().unwrap(),
+                time_slice,
+            )
+            .expect("can't get code now"),
+    )
+    .unwrap()
+    .into_raw()
+}
+
+/// # Safety
+/// A function that can be used for convenient access to the function
+/// `verify_code`, using the hash function given in the `algorithm` parameter.
+#[cfg(feature = "clib")]
+#[no_mangle]
+pub unsafe extern "C" fn verify_code_with_algorithm(
+    secret: *const c_char,
+    code: *const c_char,
+    discrepancy: u64,
+    time_slice: u64,
+    algorithm: crate::Algorithm,
+) -> bool {
+    GoogleAuthenticator::new()
+        .with_algorithm(algorithm)
+        .verify_code(
+            unsafe { CStr::from_ptr(secret) }.to_str().unwrap(),
+            unsafe { CStr::from_ptr(code) }.to_str().unwrap(),
+            discrepancy,
+            time_slice,
+        )
+}
+
+/// # Safety
+/// A function that can be used for convenient access to the function
+/// `qr_code_url`, advertising the hash function given in the `algorithm` parameter.
+#[cfg(all(feature = "qrcode", feature = "clib"))]
+#[no_mangle]
+pub unsafe extern "C" fn qr_code_url_with_algorithm(
+    secret: *const c_char,
+    name: *const c_char,
+    title: *const c_char,
+    witdh: u32,
+    height: u32,
+    level: crate::ErrorCorrectionLevel,
+    algorithm: crate::Algorithm,
+) -> *const c_char {
+    CString::new(
+        GoogleAuthenticator::new()
+            .with_algorithm(algorithm)
+            .qr_code_url(
+                unsafe { CStr::from_ptr(secret) }.to_str().unwrap(),
+                unsafe { CStr::from_ptr(name) }.to_str().unwrap(),
+                unsafe { CStr::from_ptr(title) }.to_str().unwrap(),
+                witdh,
+                height,
+                level,
+            )
+            .expect("can't get qrcode url now."),
+    )
+    .unwrap()
+    .into_raw()
+}
+
+/// # Safety
+/// A function that can be used for convenient access to the function
+/// `qr_code`, advertising the hash function given in the `algorithm` parameter.
+#[cfg(all(feature = "with-qrcode", feature = "clib"))]
+#[no_mangle]
+pub unsafe extern "C" fn qr_code_with_algorithm(
+    secret: *const c_char,
+    name: *const c_char,
+    title: *const c_char,
+    witdh: u32,
+    height: u32,
+    level: crate::ErrorCorrectionLevel,
+    algorithm: crate::Algorithm,
+) -> *const c_char {
+    CString::new(
+        GoogleAuthenticator::new()
+            .with_algorithm(algorithm)
+            .qr_code(
+                unsafe { CStr::from_ptr(secret) }.to_str().unwrap(),
+                unsafe { CStr::from_ptr(name) }.to_str().unwrap(),
+                unsafe { CStr::from_ptr(title) }.to_str().unwrap(),
+                witdh,
+                height,
+                level,
+            )
+            .expect("can't get qr code."),
+    )
+    .unwrap()
+    .into_raw()
+}
+
 /// # Safety
 /// A function that can be used for free returnd to C string
 /// `str`, the string which be passed to outside
@@ -242,7 +381,16 @@ pub unsafe extern "C" fn free_str(str: *mut c_char) {
 mod tests {
     #[cfg(feature = "with-qrcode")]
     use crate::ErrorCorrectionLevel::*;
-    use crate::GoogleAuthenticator;
+    use crate::{
+        Algorithm, AuthenticatorFile, ClockDrift, FixedClock, GoogleAuthenticator, OffsetClock,
+        PreparedSecret, RateLimit, RecoveryCodeStore, RecoveryCodes, Rejection, Secret,
+        Verification,
+    };
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     #[test]
     fn create_secret() {
@@ -260,6 +408,99 @@ mod tests {
         assert_eq!(6, auth.get_code(secret, 0).unwrap().len());
     }
 
+    #[test]
+    fn test_code_algorithms() {
+        // Test vectors from RFC 6238, appendix B, at T = 59.
+        let auth = GoogleAuthenticator::new();
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!("287082", auth.get_code(secret, 1).unwrap());
+
+        let auth = GoogleAuthenticator::new().with_algorithm(Algorithm::Sha256);
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA====";
+        assert_eq!("119246", auth.get_code(secret, 1).unwrap());
+        assert!(auth.verify_code(secret, "119246", 0, 1));
+
+        let auth = GoogleAuthenticator::new().with_algorithm(Algorithm::Sha512);
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA=";
+        assert_eq!("693936", auth.get_code(secret, 1).unwrap());
+    }
+
+    #[test]
+    fn test_code_at_epoch() {
+        // The first time slice of RFC 6238, T = 0 to 29, which is the first code of RFC 4226.
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let auth = GoogleAuthenticator::new()
+            .with_code_length(8)
+            .with_clock(FixedClock::new(1111111109));
+        assert_eq!("84755224", auth.get_code_at(secret, 0).unwrap());
+        assert!(auth.verify_code_at(secret, "84755224", 0, 0));
+        // The legacy functions use the clock for a time slice of 0.
+        assert_eq!("07081804", auth.get_code(secret, 0).unwrap());
+        assert!(!auth.verify_code(secret, "84755224", 1, 0));
+        assert!(auth.verify_code(secret, "07081804", 0, 0));
+        assert_eq!(
+            "287082",
+            GoogleAuthenticator::new().get_code_at(secret, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_code_length() {
+        // Test vectors from RFC 6238, appendix B, at T = 59.
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let auth = GoogleAuthenticator::new().with_code_length(8);
+        assert_eq!("94287082", auth.get_code(secret, 1).unwrap());
+        assert!(auth.verify_code(secret, "94287082", 0, 1));
+        let auth = GoogleAuthenticator::new().with_code_length(10);
+        assert_eq!("1094287082", auth.get_code(secret, 1).unwrap());
+
+        for code_length in [0, 5, 11] {
+            let auth = GoogleAuthenticator::new().with_code_length(code_length);
+            assert!(auth.get_code(secret, 1).is_err());
+            assert!(!auth.verify_code(secret, "94287082", 0, 1));
+        }
+    }
+
+    #[test]
+    fn test_period() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let auth = GoogleAuthenticator::new().with_period(Duration::from_secs(60));
+        let code = auth.get_code(secret, 0).unwrap();
+        assert!(auth.verify_code(secret, &code, 1, 0));
+
+        // An epoch in the future means that no period has passed yet.
+        let auth = GoogleAuthenticator::new().with_epoch(u64::MAX);
+        assert_eq!("755224", auth.get_code(secret, 0).unwrap());
+
+        let auth = GoogleAuthenticator::new().with_period(Duration::ZERO);
+        assert!(auth.get_code(secret, 0).is_err());
+        assert!(!auth.verify_code(secret, "287082", 0, 1));
+    }
+
+    #[test]
+    fn test_hotp_code() {
+        // Test vectors from RFC 4226, appendix D.
+        let auth = GoogleAuthenticator::new();
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let codes = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        for (counter, code) in codes.iter().enumerate() {
+            assert_eq!(*code, auth.get_hotp_code(secret, counter as u64).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_verify_hotp_code() {
+        let auth = GoogleAuthenticator::new();
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        assert_eq!(Some(5), auth.verify_hotp_code(secret, "338314", 4, 0));
+        assert_eq!(Some(8), auth.verify_hotp_code(secret, "162583", 4, 3));
+        assert_eq!(None, auth.verify_hotp_code(secret, "162583", 4, 2));
+        assert_eq!(None, auth.verify_hotp_code(secret, "338314", 5, 10));
+    }
+
     #[test]
     fn test_verify_code() {
         let auth = GoogleAuthenticator::new();
@@ -273,15 +514,592 @@ mod tests {
     }
 
     #[test]
-    #[cfg(feature = "with-qrcode")]
+    fn test_verify_code_once() {
+        let auth = GoogleAuthenticator::new();
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let code = auth.get_code(secret, 100).unwrap();
+        let next_code = auth.get_code(secret, 101).unwrap();
+        assert_eq!(
+            Some(100),
+            auth.verify_code_once(secret, &code, 1, 100, None)
+        );
+        assert_eq!(
+            None,
+            auth.verify_code_once(secret, &code, 1, 100, Some(100))
+        );
+        assert_eq!(
+            None,
+            auth.verify_code_once(secret, &code, 1, 101, Some(100))
+        );
+        assert_eq!(
+            None,
+            auth.verify_code_once(secret, &code, 1, 100, Some(101))
+        );
+        assert_eq!(
+            Some(101),
+            auth.verify_code_once(secret, &next_code, 1, 100, Some(100))
+        );
+    }
+
+    #[test]
+    fn test_verify_code_detailed() {
+        let auth = GoogleAuthenticator::new();
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let code = auth.get_code(secret, 100).unwrap();
+        assert_eq!(
+            Ok(Verification {
+                time_slice: 100,
+                offset: -2
+            }),
+            auth.verify_code_detailed(secret, &code, 2, 102, None)
+        );
+        assert_eq!(
+            Ok(Verification {
+                time_slice: 100,
+                offset: 1
+            }),
+            auth.verify_code_detailed(secret, &code, 2, 99, None)
+        );
+        assert_eq!(
+            Err(Rejection::OutsideWindow),
+            auth.verify_code_detailed(secret, &code, 2, 103, None)
+        );
+        assert_eq!(
+            Err(Rejection::Reused),
+            auth.verify_code_detailed(secret, &code, 2, 101, Some(100))
+        );
+        assert_eq!(
+            Err(Rejection::WrongLength),
+            auth.verify_code_detailed(secret, "12345", 2, 100, None)
+        );
+        assert_eq!(
+            Err(Rejection::NonNumeric),
+            auth.verify_code_detailed(secret, "12345a", 2, 100, None)
+        );
+        assert_eq!(
+            Err(Rejection::BadSecret),
+            auth.verify_code_detailed("GEZDGNBVGY3TQOJ!", &code, 2, 100, None)
+        );
+        let auth = GoogleAuthenticator::new().with_code_length(4);
+        assert_eq!(
+            Err(Rejection::BadConfiguration),
+            auth.verify_code_detailed(secret, "1234", 2, 100, None)
+        );
+    }
+
+    #[test]
+    fn test_clock_drift() {
+        let auth = GoogleAuthenticator::new();
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let mut drift = ClockDrift::default();
+
+        // The offset is learned from successful verifications.
+        let code = auth.get_code(secret, 99).unwrap();
+        assert_eq!(
+            -1,
+            drift.verify(&auth, secret, &code, 1, 100).unwrap().offset
+        );
+        assert_eq!(-1, drift.offset());
+        let code = auth.get_code(secret, 198).unwrap();
+        assert_eq!(
+            -2,
+            drift.verify(&auth, secret, &code, 1, 200).unwrap().offset
+        );
+        let code = auth.get_code(secret, 205).unwrap();
+        assert_eq!(
+            Err(Rejection::OutsideWindow),
+            drift.verify(&auth, secret, &code, 1, 200)
+        );
+        assert_eq!(-2, drift.offset());
+
+        // Large skews need two consecutive codes.
+        let first = auth.get_code(secret, 1000).unwrap();
+        let second = auth.get_code(secret, 1001).unwrap();
+        assert_eq!(
+            Err(Rejection::OutsideWindow),
+            drift.resync(&auth, secret, &second, &first, 100, 950)
+        );
+        assert_eq!(
+            Err(Rejection::OutsideWindow),
+            drift.resync(&auth, secret, &first, &second, 10, 950)
+        );
+        let verification = drift
+            .resync(&auth, secret, &first, &second, 100, 950)
+            .unwrap();
+        assert_eq!(1001, verification.time_slice);
+        assert_eq!(51, drift.offset());
+        let code = auth.get_code(secret, 1052).unwrap();
+        assert!(drift.verify(&auth, secret, &code, 1, 1000).is_ok());
+        assert_eq!(ClockDrift::new(52), drift);
+    }
+
+    #[test]
+    fn test_clock() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+        let clock = Arc::new(FixedClock::new(0));
+        let auth = GoogleAuthenticator::new().with_clock(clock.clone());
+        assert_eq!("755224", auth.get_code(secret, 0).unwrap());
+        clock.set(59);
+        assert_eq!("287082", auth.get_code(secret, 0).unwrap());
+        assert!(auth.verify_code(secret, "287082", 0, 0));
+        clock.advance(1);
+        assert!(!auth.verify_code(secret, "287082", 0, 0));
+        assert!(auth.verify_code(secret, "287082", 1, 0));
+
+        let auth = GoogleAuthenticator::new().with_clock(OffsetClock::new(clock.clone(), -31));
+        assert_eq!("755224", auth.get_code(secret, 0).unwrap());
+        let auth = GoogleAuthenticator::new().with_clock(OffsetClock::new(FixedClock::new(0), -1));
+        assert_eq!("755224", auth.get_code(secret, 0).unwrap());
+    }
+
+    #[test]
+    fn test_prepared_secret() {
+        let secret = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA====";
+        let prepared = PreparedSecret::new(secret, Algorithm::Sha256).unwrap();
+        assert_eq!(Algorithm::Sha256, prepared.algorithm());
+        assert_eq!(
+            "PreparedSecret { algorithm: Sha256, .. }",
+            format!("{:?}", prepared)
+        );
+
+        let auth = GoogleAuthenticator::new().with_algorithm(Algorithm::Sha256);
+        assert_eq!("119246", auth.get_code(&prepared, 1).unwrap());
+        assert!(auth.verify_code(&prepared, "119246", 1, 2));
+        assert_eq!(
+            auth.get_hotp_code(secret, 7).unwrap(),
+            auth.get_hotp_code(&prepared, 7).unwrap()
+        );
+
+        // A secret prepared for another algorithm is prepared again.
+        let auth = GoogleAuthenticator::new();
+        assert_eq!(
+            auth.get_code(secret, 1).unwrap(),
+            auth.get_code(&prepared, 1).unwrap()
+        );
+
+        for algorithm in [Algorithm::Sha1, Algorithm::Sha256, Algorithm::Sha512] {
+            let auth = GoogleAuthenticator::new().with_algorithm(algorithm);
+            let prepared = PreparedSecret::new(secret, algorithm).unwrap();
+            let key = Secret::from_base32(secret).unwrap();
+            assert_eq!(
+                auth.get_code(&key, 1).unwrap(),
+                auth.get_code(&prepared, 1).unwrap()
+            );
+        }
+
+        assert!(PreparedSecret::new("GEZDGNBVGY3TQOJ", Algorithm::Sha1).is_err());
+        assert!(PreparedSecret::new("GEZDGNBVGY3TQOJQ!", Algorithm::Sha1).is_err());
+    }
+
+    #[test]
+    fn test_generate_secret() {
+        for bits in (80..=640).step_by(8) {
+            let secret = Secret::generate(bits).unwrap();
+            assert_eq!(bits / 8, secret.expose_bytes().len());
+            let prepared = PreparedSecret::new(&secret.expose_base32(), Algorithm::Sha1).unwrap();
+            let auth = GoogleAuthenticator::new();
+            assert_eq!(
+                auth.get_code(&secret, 1).unwrap(),
+                auth.get_code(&prepared, 1).unwrap()
+            );
+        }
+        for bits in [0, 72, 161, 648] {
+            assert!(Secret::generate(bits).is_err());
+        }
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let secret = Secret::generate_with_rng(&mut rng, 160).unwrap();
+        let mut rng = StdRng::seed_from_u64(42);
+        assert!(secret == Secret::generate_with_rng(&mut rng, 160).unwrap());
+        assert!(secret != Secret::generate_with_rng(&mut rng, 160).unwrap());
+
+        let secret = GoogleAuthenticator::new().generate_secret().unwrap();
+        assert_eq!(20, secret.expose_bytes().len());
+        assert!(Secret::from_bytes(&[0; 9]).is_err());
+        assert_eq!(
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+            Secret::from_bytes(b"12345678901234567890")
+                .unwrap()
+                .expose_base32()
+        );
+    }
+
+    #[test]
+    fn test_secret() {
+        let secret = Secret::from_base32("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+        assert_eq!(b"12345678901234567890", secret.expose_bytes());
+        assert_eq!("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", secret.expose_base32());
+        assert_eq!("Secret(***)", format!("{:?}", secret));
+        assert_eq!("Secret(***)", secret.to_string());
+        assert_eq!(
+            "287082",
+            GoogleAuthenticator::new().get_code(&secret, 1).unwrap()
+        );
+        assert!(Secret::from_base32("GEZDGNBVGY3TQOJ").is_err());
+        assert!(Secret::from_base32("GEZDGNBVGY3TQOJQ!").is_err());
+        assert_eq!(secret, secret.clone());
+        assert_ne!(secret, Secret::from_bytes(b"12345678901234567891").unwrap());
+        assert_ne!(secret, Secret::from_bytes(b"1234567890123456789").unwrap());
+    }
+
+    #[test]
+    fn test_secret_encodings() {
+        let auth = GoogleAuthenticator::new();
+        let hex = Secret::from_hex("3132333435363738393031323334353637383930").unwrap();
+        assert_eq!(b"12345678901234567890", hex.expose_bytes());
+        assert_eq!("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", hex.expose_base32());
+        assert_eq!("MTIzNDU2Nzg5MDEyMzQ1Njc4OTA=", hex.expose_base64());
+        assert_eq!("287082", auth.get_code(&hex, 1).unwrap());
+        assert_eq!(
+            hex,
+            Secret::from_hex("31 32 33 34 35 36 37 38 39 30 31 32 33 34 35 36 37 38 39 30")
+                .unwrap()
+        );
+        assert!(Secret::from_hex("3132333435363738393").is_err());
+        assert!(Secret::from_hex("3132333435363738393g").is_err());
+        assert!(Secret::from_hex("313233343536373839").is_err());
+
+        let base64 = Secret::from_base64("MTIzNDU2Nzg5MDEyMzQ1Njc4OTA=").unwrap();
+        assert_eq!(hex, base64);
+        assert_eq!(
+            base64,
+            Secret::from_base64("MTIzNDU2Nzg5MDEyMzQ1Njc4OTA").unwrap()
+        );
+        let bytes = [0xfb; 20];
+        let url_safe = Secret::from_base64("-_v7-_v7-_v7-_v7-_v7-_v7-_s=").unwrap();
+        assert_eq!(&bytes, url_safe.expose_bytes());
+        assert_eq!("+/v7+/v7+/v7+/v7+/v7+/v7+/s=", url_safe.expose_base64());
+        assert_eq!("fb".repeat(20), url_safe.expose_hex());
+        assert_eq!(url_safe, Secret::from_bytes(&bytes).unwrap());
+        assert!(Secret::from_base64("MTIzNDU2Nzg5MDEyMzQ1Njc4OTA!").is_err());
+        assert!(Secret::from_base64("MTIzNDU2Nzg5").is_err());
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let recovery = RecoveryCodes::new().with_count(3).with_iterations(10);
+        let mut rng = StdRng::seed_from_u64(42);
+        let codes = recovery.generate_with_rng(&mut rng).unwrap();
+        assert_eq!(3, codes.len());
+        for code in &codes {
+            assert_eq!(14, code.len());
+            assert_eq!(Some('-'), code.chars().nth(4));
+            assert_eq!(Some('-'), code.chars().nth(9));
+        }
+        assert_ne!(codes[0], codes[1]);
+        assert_ne!(codes, recovery.generate_with_rng(&mut rng).unwrap());
+        assert_eq!(
+            5,
+            RecoveryCodes::new().with_count(5).generate().unwrap().len()
+        );
+        assert!(RecoveryCodes::new().with_length(7).generate().is_err());
+        assert_eq!(
+            39,
+            RecoveryCodes::new().with_length(32).generate().unwrap()[0].len()
+        );
+
+        let hash = recovery.hash(&codes[0]).unwrap();
+        assert!(hash.starts_with("$pbkdf2-sha256$i=10$"));
+        assert_ne!(hash, recovery.hash(&codes[0]).unwrap());
+        assert!(recovery.verify(&codes[0], &hash));
+        assert!(recovery.verify(&codes[0].to_uppercase().replace('-', " "), &hash));
+        assert!(!recovery.verify(&codes[1], &hash));
+        assert!(!recovery.verify(&codes[0], "$pbkdf2-sha256$i=10$AAAA"));
+        assert!(recovery.clone().with_iterations(0).hash(&codes[0]).is_err());
+        assert!(recovery.verify("0123-ilou", &recovery.hash("o123-1l0u").unwrap()));
+
+        let mut store = codes
+            .iter()
+            .map(|code| recovery.hash(code).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(Ok(None), recovery.redeem(&mut store, "0000-0000-0000"));
+        assert_eq!(Ok(Some(2)), recovery.redeem(&mut store, &codes[1]));
+        assert_eq!(Ok(None), recovery.redeem(&mut store, &codes[1]));
+        assert_eq!(Ok(2), store.remaining());
+
+        let mut store = store.into_iter().collect::<HashSet<_>>();
+        assert_eq!(Ok(Some(1)), recovery.redeem(&mut store, &codes[0]));
+        assert_eq!(Ok(Some(0)), recovery.redeem(&mut store, &codes[2]));
+        assert_eq!(Ok(None), recovery.redeem(&mut store, &codes[2]));
+        assert_eq!(Ok(0), store.remaining());
+
+        // The codes of an account share a salt, so a redemption derives a single key.
+        let mut store = recovery.hash_all_with_rng(&mut rng, &codes).unwrap();
+        let salt = |hash: &str| hash.rsplit_once('$').unwrap().0.to_string();
+        assert!(store.iter().all(|hash| salt(hash) == salt(&store[0])));
+        assert!(codes.iter().zip(&store).all(|(c, h)| recovery.verify(c, h)));
+        store.push(recovery.hash("0123-4567-89ab").unwrap());
+        store.push("malformed".to_string());
+        assert_eq!(Ok(None), recovery.redeem(&mut store, "0000-0000-0000"));
+        assert_eq!(Ok(Some(4)), recovery.redeem(&mut store, &codes[2]));
+        assert_eq!(Ok(Some(3)), recovery.redeem(&mut store, "0123-4567-89ab"));
+        assert_eq!(Ok(None), recovery.redeem(&mut store, &codes[2]));
+    }
+
+    #[test]
+    fn test_authenticator_file() {
+        let content = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\n\
+            \" RATE_LIMIT 3 30 1111111110\n\
+            \" WINDOW_SIZE 5\n\
+            \" DISALLOW_REUSE 37037036\n\
+            \" TOTP_AUTH\n\
+            \" STEP_SIZE 30\n\
+            \" TIME_SKEW 0\n\
+            12345678\n\
+            87654321\n";
+        let mut file: AuthenticatorFile = content.parse().unwrap();
+        let debug = format!("{:?}", file);
+        assert!(debug.contains("secret: ***") && debug.contains("scratch_codes: [***, ***]"));
+        assert!(!debug.contains("GEZDGNBVGY3TQOJQ") && !debug.contains("12345678"));
+        assert_eq!(
+            Some(RateLimit {
+                attempts: 3,
+                interval: 30,
+                timestamps: vec![1111111110],
+            }),
+            file.rate_limit
+        );
+        assert_eq!(Some(5), file.window_size);
+        assert_eq!(Some(vec![37037036]), file.disallow_reuse);
+        assert!(file.totp_auth);
+        assert_eq!(None, file.hotp_counter);
+        assert_eq!(Some(30), file.step_size);
+        assert_eq!(vec!["TIME_SKEW 0"], file.other_options);
+        assert_eq!(vec!["12345678", "87654321"], file.scratch_codes);
+        assert_eq!(content, file.to_string());
+
+        // RFC 6238 test vector at 1111111109, which is time slice 37037036.
+        assert!(!file.verify("081804", 1111111109).unwrap());
+        assert!(file.verify("050471", 1111111111).unwrap());
+        assert_eq!(Some(vec![37037036, 37037037]), file.disallow_reuse);
+        assert!(!file.verify("050471", 1111111111).unwrap());
+        // The rate limit of 3 attempts in 30 seconds was reached.
+        assert!(!file.verify("12345678", 1111111112).unwrap());
+        assert!(file.verify("12345678", 1111111150).unwrap());
+        assert!(!file.verify("12345678", 1111111151).unwrap());
+        assert_eq!(vec!["87654321"], file.scratch_codes);
+
+        // An even window of 4 accepts one time slice before and two after the current one.
+        let auth = GoogleAuthenticator::new();
+        let mut file = AuthenticatorFile::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        file.window_size = Some(4);
+        let code = |time_slice| auth.get_code_at(&file.secret, time_slice).unwrap();
+        let (early, late, too_early, too_late) = (code(99), code(102), code(98), code(103));
+        assert!(!file.verify(&too_early, 100 * 30).unwrap());
+        assert!(file.verify(&early, 100 * 30).unwrap());
+        assert!(file.verify(&late, 100 * 30).unwrap());
+        assert!(!file.verify(&too_late, 100 * 30).unwrap());
+
+        let mut file = AuthenticatorFile::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        file.totp_auth = false;
+        file.hotp_counter = Some(1);
+        assert_eq!(
+            "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\n\" HOTP_COUNTER 1\n",
+            file.to_string()
+        );
+        // RFC 4226 test vectors for the counters 3 and 4, with the default window of 3.
+        assert!(file.verify("969429", 0).unwrap());
+        assert_eq!(Some(4), file.hotp_counter);
+        assert!(!file.verify("969429", 0).unwrap());
+        assert_eq!(Some(5), file.hotp_counter);
+        file.hotp_counter = None;
+        assert!(file.verify("338314", 0).is_err());
+
+        let mut rng = StdRng::seed_from_u64(42);
+        file.generate_scratch_codes_with_rng(&mut rng, 5);
+        assert_eq!(5, file.scratch_codes.len());
+        assert!(file
+            .scratch_codes
+            .iter()
+            .all(|code| code.len() == 8 && !code.starts_with('0')));
+
+        assert!("".parse::<AuthenticatorFile>().is_err());
+        assert!("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\n1234567\n"
+            .parse::<AuthenticatorFile>()
+            .is_err());
+        assert!("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\n\" WINDOW_SIZE x\n"
+            .parse::<AuthenticatorFile>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_authenticator_file_save() {
+        let path =
+            std::env::temp_dir().join(format!("google_authenticator_test_{}", std::process::id()));
+        let mut file = AuthenticatorFile::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        file.disallow_reuse = Some(Vec::new());
+        file.scratch_codes.push("12345678".to_string());
+        file.save(&path).unwrap();
+        assert_eq!(file, AuthenticatorFile::load(&path).unwrap());
+
+        assert!(AuthenticatorFile::verify_file(&path, "12345678", 1111111109).unwrap());
+        assert!(!AuthenticatorFile::verify_file(&path, "12345678", 1111111109).unwrap());
+        assert!(AuthenticatorFile::verify_file(&path, "081804", 1111111109).unwrap());
+        assert!(!AuthenticatorFile::verify_file(&path, "081804", 1111111109).unwrap());
+        let file = AuthenticatorFile::load(&path).unwrap();
+        assert_eq!(Some(vec![37037036]), file.disallow_reuse);
+        assert!(file.scratch_codes.is_empty());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+
+            // The temporary file is never followed to another file.
+            let target = path.with_extension("target");
+            let temp_path =
+                path.with_file_name(format!("{}~", path.file_name().unwrap().to_str().unwrap()));
+            std::fs::write(&target, "unchanged").unwrap();
+            std::os::unix::fs::symlink(&target, &temp_path).unwrap();
+            assert!(file.save(&path).is_err());
+            assert_eq!("unchanged", std::fs::read_to_string(&target).unwrap());
+            std::fs::remove_file(&temp_path).unwrap();
+            std::fs::remove_file(&target).unwrap();
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "pam")]
+    fn test_pam_options() {
+        use crate::pam::{split_code, PamOptions};
+        use std::path::PathBuf;
+
+        let options = PamOptions::parse([]).unwrap();
+        assert_eq!(
+            PathBuf::from("/home/alice/.google_authenticator"),
+            options.secret_path("alice", "/home/alice")
+        );
+        assert!(!options.nullok && !options.forward_pass);
+        assert_eq!(None, options.window_size);
+
+        let options = PamOptions::parse([
+            "secret=/var/lib/ga/${USER}",
+            "nullok",
+            "window_size=5",
+            "forward_pass",
+        ])
+        .unwrap();
+        assert_eq!(
+            PathBuf::from("/var/lib/ga/alice"),
+            options.secret_path("alice", "/home/alice")
+        );
+        assert!(options.nullok && options.forward_pass);
+        assert_eq!(Some(5), options.window_size);
+        assert_eq!(
+            PathBuf::from("/home/alice/ga/alice"),
+            PamOptions::parse(["secret=${HOME}/ga/${USER}"])
+                .unwrap()
+                .secret_path("alice", "/home/alice")
+        );
+        assert_eq!(None, PamOptions::parse(["window_size=0"]));
+        assert_eq!(None, PamOptions::parse(["unknown"]));
+
+        let mut file = AuthenticatorFile::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        file.scratch_codes.push("12345678".to_string());
+        assert_eq!(
+            Some(("hunter2", "123456")),
+            split_code("hunter2123456", &file)
+        );
+        assert_eq!(
+            Some(("hunter2", "12345678")),
+            split_code("hunter212345678", &file)
+        );
+        assert_eq!(
+            Some(("hunter212", "345679")),
+            split_code("hunter212345679", &file)
+        );
+        assert_eq!(Some(("", "123456")), split_code("123456", &file));
+        assert_eq!(None, split_code("hunter2", &file));
+        assert_eq!(None, split_code("12345", &file));
+    }
+
+    #[test]
+    fn test_normalize_secret() {
+        let auth = GoogleAuthenticator::new();
+        for secret in [
+            "gezdgnbvgy3tqojqgezdgnbvgy3tqojq",
+            "GEZD GNBV GY3T QOJQ GEZD GNBV GY3T QOJQ",
+            "gezd-gnbv-gy3t-qojq-gezd-gnbv-gy3t-qojq",
+        ] {
+            assert_eq!(
+                "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+                Secret::normalize(secret).unwrap()
+            );
+            assert_eq!("287082", auth.get_code(secret, 1).unwrap());
+            assert!(auth.verify_code(secret, "287082", 0, 1));
+        }
+        assert_eq!(
+            "GEZDGNBVGY3TQOJQGEZA====",
+            Secret::normalize("GEZDGNBVGY3TQOJQGEZA").unwrap()
+        );
+        assert_eq!(
+            "GEZDGNBVGY3TQOJQGEZA====",
+            Secret::normalize("GEZDGNBVGY3TQOJQGEZA=").unwrap()
+        );
+        // The decoded length is checked, not the number of characters.
+        assert!(Secret::normalize("GEZD GNBV GY3T QOJ").is_err());
+        assert!(Secret::normalize("GEZDGNBVGY3TQOJQG").is_err());
+        assert!(Secret::normalize("GEZDGNBVGY3TQOJQ1").is_err());
+        assert_eq!(
+            "otpauth://totp/alice?secret=GEZDGNBVGY3TQOJQGEZA&algorithm=SHA1&digits=6&period=30",
+            auth.otp_auth_uri("gezd gnbv gy3t qojq geza", "alice", "")
+                .to_string()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "secrecy")]
+    fn test_secrecy() {
+        use secrecy::{ExposeSecret, SecretString};
+
+        let secret = Secret::from_base32("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+        assert_eq!(b"12345678901234567890", secret.expose_secret());
+        let secret = SecretString::from("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(
+            "287082",
+            GoogleAuthenticator::new().get_code(&secret, 1).unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "qrcode")]
     fn test_qr_code_url() {
+        use crate::ErrorCorrectionLevel::*;
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
         let auth = GoogleAuthenticator::new();
         let secret = "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3";
-        let url = auth.qr_code_url(secret, "secret code", "hi there", 0, 0, Medium);
-        println!("{}", url);
-        let resp = ureq::get(&url).call();
-        assert!(resp)
-        // panic!();
+        let url = auth
+            .qr_code_url(secret, "secret code", "hi there", 0, 0, Medium)
+            .unwrap();
+        let svg = STANDARD
+            .decode(url.strip_prefix("data:image/svg+xml;base64,").unwrap())
+            .unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(!url.contains("googleapis"));
+
+        let large = auth
+            .qr_code_url(secret, "secret code", "hi there", 400, 300, High)
+            .unwrap();
+        let large = String::from_utf8(
+            STANDARD
+                .decode(large.strip_prefix("data:image/svg+xml;base64,").unwrap())
+                .unwrap(),
+        )
+        .unwrap();
+        let width = |svg: &str| -> u32 {
+            let start = svg.find("width=\"").unwrap() + 7;
+            svg[start..start + svg[start..].find('"').unwrap()]
+                .parse()
+                .unwrap()
+        };
+        assert!(width(&svg) >= 200);
+        assert!(width(&large) >= 400);
+        assert!(auth
+            .qr_code_url(secret, &"A".repeat(4000), "", 0, 0, High)
+            .is_err());
     }
 
     #[test]
@@ -292,6 +1110,490 @@ mod tests {
         auth.qr_code(secret, "secret_code", "hi", 0, 0, Medium)
             .unwrap();
     }
+
+    #[test]
+    #[cfg(feature = "with-qrcode")]
+    fn test_qr_code_png() {
+        use crate::{Color, QrStyle};
+
+        let auth = GoogleAuthenticator::new();
+        let secret = "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3";
+        let png = auth
+            .qr_code_png(secret, "secret_code", "hi", 0, 0, Medium)
+            .unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(image.width(), image.height());
+        assert!(image.width() >= 200);
+        assert_eq!(&[255, 255, 255, 255], &image.get_pixel(0, 0).0);
+
+        let uri = auth.otp_auth_uri(secret, "secret_code", "hi").to_string();
+        let style = QrStyle::new()
+            .with_dark_color("#1a237e".parse().unwrap())
+            .with_light_color(Color::rgba(255, 255, 0, 128))
+            .with_quiet_zone(2)
+            .with_module_size(3);
+        let png = style.png(&uri, 1000, 1000, High).unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgba8();
+        let modules = image.width() / 3;
+        assert_eq!(image.width(), modules * 3);
+        assert_eq!(&[255, 255, 0, 128], &image.get_pixel(5, 5).0);
+        // The top left finder pattern starts after the quiet zone.
+        assert_eq!(&[0x1a, 0x23, 0x7e, 255], &image.get_pixel(6, 6).0);
+        assert_eq!(&[0x1a, 0x23, 0x7e, 255], &image.get_pixel(8, 8).0);
+
+        let png = QrStyle::new().png(&uri, 300, 100, Low).unwrap();
+        assert!(image::load_from_memory(&png).unwrap().to_rgba8().width() >= 300);
+
+        assert_eq!("#1a237e", Color::rgb(0x1a, 0x23, 0x7e).to_string());
+        assert_eq!("#1a237e80", Color::rgba(0x1a, 0x23, 0x7e, 0x80).to_string());
+        assert_eq!(
+            Color::rgba(0x1a, 0x23, 0x7e, 0x80),
+            "#1A237E80".parse().unwrap()
+        );
+        assert!("1a237e".parse::<Color>().is_err());
+        assert!("#1a237".parse::<Color>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "with-qrcode")]
+    fn test_qr_style() {
+        use crate::{Color, Logo, QrStyle};
+
+        let uri = GoogleAuthenticator::new()
+            .otp_auth_uri("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", "secret_code", "hi")
+            .to_string();
+        let svg = QrStyle::new()
+            .with_dark_color("#1a237e".parse().unwrap())
+            .with_light_color(Color::rgba(255, 255, 0, 128))
+            .with_quiet_zone(2)
+            .with_module_size(3)
+            .svg(&uri, 0, 0, Low)
+            .unwrap();
+        assert!(svg.contains(r##"fill="#1a237e""##));
+        assert!(svg.contains(r##"fill="#ffff00" fill-opacity="0.502""##));
+        // The top left finder pattern starts after the quiet zone.
+        assert!(svg.contains(r#"d="M2 2h1v1h-1z"#));
+        assert!(!svg.contains("<image"));
+
+        let rounded = QrStyle::new()
+            .with_rounded_modules(true)
+            .svg(&uri, 0, 0, Low)
+            .unwrap();
+        assert!(rounded.contains(r#"d="M4.3 4h.4a.3 .3 0 0 1 .3 .3"#));
+        assert_eq!(svg.matches('z').count(), rounded.matches('z').count());
+
+        // A logo raises the error correction level, which makes the code larger.
+        let view_box = |svg: &str| svg.split("viewBox=").nth(1).unwrap()[..16].to_string();
+        let logo = r#"<svg xmlns="http://www.w3.org/2000/svg"/>"#;
+        let style = QrStyle::new().with_logo(Logo::Svg(logo.to_string()));
+        let with_logo = style.svg(&uri, 0, 0, Low).unwrap();
+        assert!(with_logo.contains(r#"<image x="#));
+        let high = QrStyle::new().svg(&uri, 0, 0, High).unwrap();
+        assert_eq!(view_box(&high), view_box(&with_logo));
+        let low = QrStyle::new().svg(&uri, 0, 0, Low).unwrap();
+        assert_ne!(view_box(&low), view_box(&with_logo));
+        assert!(style.png(&uri, 0, 0, Low).is_err());
+        assert!(style
+            .clone()
+            .with_logo_size(31)
+            .svg(&uri, 0, 0, Low)
+            .is_err());
+
+        let logo = QrStyle::new()
+            .with_dark_color(Color::rgb(255, 0, 0))
+            .png("logo", 0, 0, Low)
+            .unwrap();
+        let png = QrStyle::new()
+            .with_module_size(10)
+            .with_rounded_modules(true)
+            .with_logo(Logo::Png(logo))
+            .png(&uri, 0, 0, Low)
+            .unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgba8();
+        assert!(image
+            .pixels()
+            .any(|pixel| pixel.0[0] > 200 && pixel.0[1] < 50 && pixel.0[2] < 50));
+        // The corners of rounded modules are light.
+        assert_eq!(&[255, 255, 255, 255], &image.get_pixel(40, 40).0);
+        assert_eq!(&[0, 0, 0, 255], &image.get_pixel(45, 45).0);
+        assert!(QrStyle::new()
+            .with_logo(Logo::Png(b"not a png".to_vec()))
+            .png(&uri, 0, 0, Low)
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "qr-decode", feature = "with-qrcode"))]
+    fn test_decode_qr_code() {
+        use crate::{decode_qr_code, Color, Logo, OtpAuthUri, QrStyle};
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let auth = GoogleAuthenticator::new().with_code_length(8);
+        let secret = "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3";
+        let uri = auth.otp_auth_uri(secret, "secret_code", "hi");
+        let text = uri.to_string();
+
+        let svg = auth
+            .qr_code(secret, "secret_code", "hi", 0, 0, Medium)
+            .unwrap();
+        assert_eq!(text, decode_qr_code(svg.as_bytes()).unwrap());
+        let url = auth
+            .qr_code_url(secret, "secret_code", "hi", 0, 0, Low)
+            .unwrap();
+        let svg = STANDARD
+            .decode(url.trim_start_matches("data:image/svg+xml;base64,"))
+            .unwrap();
+        assert_eq!(text, decode_qr_code(&svg).unwrap());
+        let png = auth
+            .qr_code_png(secret, "secret_code", "hi", 0, 0, High)
+            .unwrap();
+        assert_eq!(text, decode_qr_code(&png).unwrap());
+        assert_eq!(uri, OtpAuthUri::from_qr_code(&png).unwrap());
+        let svg = auth
+            .hotp_qr_code(secret, "secret_code", "hi", 42, 0, 0, Quartile)
+            .unwrap();
+        let hotp = OtpAuthUri::from_qr_code(svg.as_bytes()).unwrap();
+        assert_eq!(42, hotp.counter);
+
+        let logo = QrStyle::new()
+            .with_dark_color(Color::rgb(255, 0, 0))
+            .png("logo", 0, 0, Low)
+            .unwrap();
+        let style = QrStyle::new()
+            .with_dark_color("#1a237e".parse().unwrap())
+            .with_light_color(Color::rgba(255, 255, 255, 0))
+            .with_rounded_modules(true)
+            .with_logo_size(30);
+        let png = style
+            .clone()
+            .with_logo(Logo::Png(logo))
+            .png(&text, 0, 0, Low)
+            .unwrap();
+        assert_eq!(text, decode_qr_code(&png).unwrap());
+        let svg = style
+            .with_logo(Logo::Svg(
+                r#"<svg xmlns="http://www.w3.org/2000/svg"/>"#.into(),
+            ))
+            .svg(&text, 0, 0, Low)
+            .unwrap();
+        assert_eq!(text, decode_qr_code(svg.as_bytes()).unwrap());
+
+        // Light modules on a dark background, compressed as JPEG.
+        let png = QrStyle::new()
+            .with_dark_color(Color::WHITE)
+            .with_light_color(Color::BLACK)
+            .png(&text, 0, 0, Medium)
+            .unwrap();
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, 80)
+            .encode_image(&image::load_from_memory(&png).unwrap().to_rgb8())
+            .unwrap();
+        assert_eq!(text, decode_qr_code(&jpeg).unwrap());
+
+        let blank = QrStyle::new()
+            .with_dark_color(Color::WHITE)
+            .png("", 0, 0, Low)
+            .unwrap();
+        assert!(decode_qr_code(&blank).is_err());
+        assert!(decode_qr_code(b"not an image").is_err());
+        assert!(decode_qr_code(b"<svg").is_err());
+
+        // Images that would use too much memory are rejected from their header.
+        let too_large = |result: crate::Result<String>| matches!(result, Err(crate::GAError::Error(e)) if e.starts_with("image too large"));
+        let chunk = |kind: &[u8], data: &[u8]| {
+            let mut crc = !0u32;
+            for &b in kind.iter().chain(data) {
+                crc ^= b as u32;
+                for _ in 0..8 {
+                    crc = if crc & 1 == 1 {
+                        (crc >> 1) ^ 0xedb88320
+                    } else {
+                        crc >> 1
+                    };
+                }
+            }
+            [
+                &(data.len() as u32).to_be_bytes(),
+                kind,
+                data,
+                &(!crc).to_be_bytes(),
+            ]
+            .concat()
+        };
+        let header = [
+            &50000u32.to_be_bytes()[..],
+            &50000u32.to_be_bytes(),
+            &[8, 0, 0, 0, 0],
+        ];
+        let png = [
+            &b"\x89PNG\r\n\x1a\n"[..],
+            &chunk(b"IHDR", &header.concat()),
+            &chunk(b"IDAT", &[]),
+            &chunk(b"IEND", &[]),
+        ]
+        .concat();
+        assert!(too_large(decode_qr_code(&png)));
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="1000000"/>"#;
+        assert!(too_large(decode_qr_code(svg.as_bytes())));
+        let png = QrStyle::new()
+            .png("https://example.com", 0, 0, Low)
+            .unwrap();
+        assert!(OtpAuthUri::from_qr_code(&png).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "with-qrcode")]
+    fn test_qr_code_terminal() {
+        use crate::TerminalQr;
+
+        let auth = GoogleAuthenticator::new();
+        let secret = "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3";
+        let uri = auth.otp_auth_uri(secret, "secret_code", "hi");
+        let text = auth
+            .qr_code_terminal(secret, "secret_code", "hi", Medium)
+            .unwrap();
+        assert_eq!(text, uri.qr_code_terminal(Medium).unwrap());
+        // The URI is printed below the QR code.
+        let (code, uri_line) = text.trim_end().rsplit_once('\n').unwrap();
+        assert_eq!(uri.to_string(), uri_line);
+        let lines: Vec<Vec<char>> = code.lines().map(|line| line.chars().collect()).collect();
+        let size = lines[0].len();
+        assert_eq!(lines.len(), size.div_ceil(2));
+        assert!(lines.iter().all(|line| line.len() == size));
+        // The quiet zone is two modules wide, then the finder pattern starts.
+        assert_eq!(&[' ', ' ', '\u{2588}'], &lines[1][..3]);
+        assert!(code
+            .chars()
+            .all(|c| "\n \u{2580}\u{2584}\u{2588}".contains(c)));
+        assert_eq!(
+            format!("{}\n", code),
+            TerminalQr::new()
+                .with_uri(false)
+                .render(&uri.to_string(), Medium)
+                .unwrap()
+        );
+
+        let inverted = TerminalQr::new()
+            .with_inverted(true)
+            .with_compact(false)
+            .with_quiet_zone(1)
+            .with_uri(false)
+            .render(&uri.to_string(), Medium)
+            .unwrap();
+        let lines: Vec<&str> = inverted.lines().collect();
+        assert_eq!(lines.len(), size - 2);
+        assert_eq!(lines[0], "\u{2588}".repeat(2 * (size - 2)));
+        assert!(lines[1].starts_with("\u{2588}\u{2588}  "));
+
+        let ansi = TerminalQr::new()
+            .with_ansi(true)
+            .render(&uri.to_string(), Medium)
+            .unwrap();
+        assert_eq!(ansi.lines().count(), size.div_ceil(2) + 1);
+        assert!(ansi.starts_with("\x1b[97;107m\u{2580}"));
+        assert!(ansi
+            .lines()
+            .take(size.div_ceil(2))
+            .all(|line| line.ends_with("\x1b[0m")));
+        assert_eq!(Some(uri.to_string().as_str()), ansi.lines().last());
+
+        assert!(TerminalQr::new().render(&"A".repeat(4000), High).is_err());
+    }
+}
+
+#[cfg(test)]
+mod uri_tests {
+    use crate::{Algorithm, GoogleAuthenticator, OtpAuthUri, OtpType};
+    use std::time::Duration;
+
+    #[test]
+    fn parse_totp() {
+        let uri: OtpAuthUri =
+            "otpauth://totp/Example:alice@google.com?secret=JBSWY3DPEHPK3PXP&issuer=Example"
+                .parse()
+                .unwrap();
+        assert_eq!(OtpType::Totp, uri.otp_type);
+        assert_eq!("JBSWY3DPEHPK3PXP", uri.secret);
+        assert_eq!("alice@google.com", uri.account);
+        assert_eq!(Some("Example".to_string()), uri.issuer);
+        assert_eq!(Algorithm::Sha1, uri.algorithm);
+        assert_eq!(6, uri.digits);
+        assert_eq!(30, uri.period);
+        assert!(uri.parameters.is_empty());
+        let debug = format!("{:?}", uri);
+        assert!(debug.contains("secret: ***") && debug.contains("alice@google.com"));
+        assert!(!debug.contains("JBSWY3DPEHPK3PXP"));
+    }
+
+    #[test]
+    fn parse_all_parameters() {
+        let uri: OtpAuthUri = "otpauth://hotp/ACME%20Co%3A%20john.doe%40email.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=ACME%20Co&algorithm=sha512&digits=8&period=60&counter=4&image=https%3A%2F%2Fexample.com%2Flogo.png"
+            .parse()
+            .unwrap();
+        assert_eq!(OtpType::Hotp, uri.otp_type);
+        assert_eq!("john.doe@email.com", uri.account);
+        assert_eq!(Some("ACME Co".to_string()), uri.issuer);
+        assert_eq!(Algorithm::Sha512, uri.algorithm);
+        assert_eq!(8, uri.digits);
+        assert_eq!(60, uri.period);
+        assert_eq!(4, uri.counter);
+        assert_eq!(
+            vec![(
+                "image".to_string(),
+                "https://example.com/logo.png".to_string()
+            )],
+            uri.parameters
+        );
+    }
+
+    #[test]
+    fn parse_issuer_from_label() {
+        let uri: OtpAuthUri = "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP"
+            .parse()
+            .unwrap();
+        assert_eq!(Some("Example".to_string()), uri.issuer);
+        let uri: OtpAuthUri = "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP"
+            .parse()
+            .unwrap();
+        assert_eq!(None, uri.issuer);
+        assert_eq!("alice", uri.account);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for uri in [
+            "https://totp/alice?secret=JBSWY3DPEHPK3PXP",
+            "otpauth://motp/alice?secret=JBSWY3DPEHPK3PXP",
+            "otpauth://totp/?secret=JBSWY3DPEHPK3PXP",
+            "otpauth://totp/alice",
+            "otpauth://totp/alice?secret=not%20base32",
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=MD5",
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits=4",
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&period=0",
+            "otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP",
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&issuer=%FF",
+        ] {
+            assert!(uri.parse::<OtpAuthUri>().is_err(), "{}", uri);
+        }
+    }
+
+    #[test]
+    fn build() {
+        let uri = OtpAuthUri::new("JBSWY3DPEHPK3PXP", "alice@google.com").with_issuer("Example");
+        assert_eq!(
+            "otpauth://totp/Example:alice%40google.com?secret=JBSWY3DPEHPK3PXP&issuer=Example&algorithm=SHA1&digits=6&period=30",
+            uri.to_string()
+        );
+        let uri = OtpAuthUri::new("JBSWY3DPEHPK3PXP", "alice")
+            .with_counter(7)
+            .with_parameter("image", "https://example.com/logo.png");
+        assert_eq!(
+            "otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1&digits=6&counter=7&image=https%3A%2F%2Fexample.com%2Flogo.png",
+            uri.to_string()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let uri = OtpAuthUri::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", "john:doe@example.com")
+            .with_issuer("ACME Co")
+            .with_algorithm(Algorithm::Sha256)
+            .with_digits(8)
+            .with_period(Duration::from_secs(60))
+            .with_parameter("image", "https://example.com/logo.png?size=large&dark");
+        assert!(uri
+            .to_string()
+            .starts_with("otpauth://totp/ACME%20Co:john%3Adoe%40example.com?"));
+        assert_eq!(uri, uri.to_string().parse().unwrap());
+
+        let uri = OtpAuthUri::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", "alice").with_counter(42);
+        assert_eq!(uri, uri.to_string().parse().unwrap());
+
+        let uri = OtpAuthUri::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", "a:b");
+        assert!(uri.to_string().starts_with("otpauth://totp/a%3Ab?"));
+        assert_eq!(uri, uri.to_string().parse().unwrap());
+    }
+
+    #[test]
+    fn validate() {
+        let uri = OtpAuthUri::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", "alice");
+        assert!(uri.validate().is_ok());
+        assert!(uri.clone().with_digits(4).validate().is_err());
+        assert!(uri.clone().with_digits(11).validate().is_err());
+        assert!(uri
+            .clone()
+            .with_period(Duration::from_millis(500))
+            .validate()
+            .is_err());
+        assert!(uri
+            .clone()
+            .with_parameter("digits", "8")
+            .validate()
+            .is_err());
+        assert!(OtpAuthUri::new("", "alice").validate().is_err());
+        assert!(OtpAuthUri::new("GEZDGNBVGY3TQOJQ!", "alice")
+            .validate()
+            .is_err());
+        assert!(OtpAuthUri::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", "")
+            .validate()
+            .is_err());
+        assert!(
+            OtpAuthUri::new("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", " alice")
+                .validate()
+                .is_err()
+        );
+        #[cfg(feature = "qrcode")]
+        assert!(uri
+            .with_digits(4)
+            .qr_code_url(0, 0, crate::ErrorCorrectionLevel::Medium)
+            .is_err());
+    }
+
+    #[test]
+    fn parse_encoded_colon() {
+        let uri: OtpAuthUri = "otpauth://totp/a%3Ab?secret=JBSWY3DPEHPK3PXP"
+            .parse()
+            .unwrap();
+        assert_eq!("a:b", uri.account);
+        assert_eq!(None, uri.issuer);
+
+        let uri: OtpAuthUri = "otpauth://totp/A%3Ab%3Ac%3Ad?secret=JBSWY3DPEHPK3PXP&issuer=A%3Ab"
+            .parse()
+            .unwrap();
+        assert_eq!("c:d", uri.account);
+        assert_eq!(Some("A:b".to_string()), uri.issuer);
+
+        let uri: OtpAuthUri = "otpauth://totp/Other%3Aalice?secret=JBSWY3DPEHPK3PXP&issuer=ACME"
+            .parse()
+            .unwrap();
+        assert_eq!("alice", uri.account);
+        assert_eq!(Some("ACME".to_string()), uri.issuer);
+    }
+
+    #[test]
+    fn authenticator_uri() {
+        let auth = GoogleAuthenticator::new()
+            .with_algorithm(Algorithm::Sha512)
+            .with_code_length(8)
+            .with_period(Duration::from_secs(60));
+        let uri = auth.otp_auth_uri("JBSWY3DPEHPK3PXP", "alice", "Example");
+        assert_eq!(
+            "otpauth://totp/Example:alice?secret=JBSWY3DPEHPK3PXP&issuer=Example&algorithm=SHA512&digits=8&period=60",
+            uri.to_string()
+        );
+        let uri = auth.otp_auth_uri("JBSWY3DPEHPK3PXP", "alice", "");
+        assert_eq!(None, uri.issuer);
+    }
+
+    #[test]
+    fn authenticator() {
+        let uri: OtpAuthUri =
+            "otpauth://totp/alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&digits=8"
+                .parse()
+                .unwrap();
+        let auth = uri.authenticator();
+        assert_eq!("94287082", auth.get_code(&uri.secret, 1).unwrap());
+    }
 }
 
 #[cfg(test)]
@@ -324,9 +1626,11 @@ mod macro_tests {
         assert!(qr_code!(secret, "qr_code", "name").is_ok());
     }
     #[test]
-    #[cfg(feature = "with-qrcode")]
+    #[cfg(feature = "qrcode")]
     fn test_qr_code_url() {
         let secret = "I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3";
-        qr_code_url!(secret, "qr_code", "name");
+        assert!(qr_code_url!(secret, "qr_code", "name")
+            .unwrap()
+            .starts_with("data:image/svg+xml;base64,"));
     }
 }
@@ -0,0 +1,540 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The `google-authenticator` command line tool, built with the feature flag `cli`. It enrolls
+//! accounts, shows and verifies codes, and builds and parses `otpauth://` URIs.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use google_authenticator::{
+    Algorithm, Clock, ErrorCorrectionLevel, FixedClock, GAError, GoogleAuthenticator, OtpAuthUri,
+    OtpType, Result, Secret, SystemClock, TerminalQr,
+};
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "google-authenticator", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a new secret and show its QR code.
+    New {
+        /// The account name shown in the app.
+        #[arg(long, default_value = "user")]
+        account: String,
+        /// The issuer shown in the app.
+        #[arg(long)]
+        issuer: Option<String>,
+        /// The number of bits of the secret.
+        #[arg(long, default_value_t = 160)]
+        bits: usize,
+        /// Draw the QR code with ANSI colours instead of Unicode blocks.
+        #[arg(long)]
+        ansi: bool,
+        /// Draw the QR code for a terminal with a light background, without `--ansi`.
+        #[arg(long)]
+        light_background: bool,
+        #[command(flatten)]
+        config: Config,
+    },
+    /// Show the current code, or the code at a given time or counter.
+    Code {
+        #[command(flatten)]
+        secret: SecretArg,
+        /// The unix time of the code, instead of now.
+        #[arg(long, conflicts_with = "counter")]
+        time: Option<u64>,
+        /// The counter of a counter based (HOTP) code.
+        #[arg(long)]
+        counter: Option<u64>,
+        /// Keep showing the current code with a countdown.
+        #[arg(long, conflicts_with_all = ["time", "counter"])]
+        watch: bool,
+        #[command(flatten)]
+        config: Config,
+    },
+    /// Verify a code. Exits with a failure if the code is rejected.
+    Verify {
+        #[command(flatten)]
+        secret: SecretArg,
+        /// The code to verify.
+        code: String,
+        /// The number of time slices before and after the current one that are accepted.
+        #[arg(long, default_value_t = 1)]
+        window: u64,
+        /// The unix time of the verification, instead of now.
+        #[arg(long)]
+        time: Option<u64>,
+        #[command(flatten)]
+        config: Config,
+    },
+    /// Build or parse an otpauth:// URI.
+    Uri {
+        #[command(subcommand)]
+        command: UriCommand,
+    },
+    /// Write the QR code of an account to an SVG or PNG file.
+    Qr {
+        #[command(flatten)]
+        account: AccountArgs,
+        /// The file to write.
+        #[arg(long, short)]
+        output: PathBuf,
+        /// The format of the file, guessed from its extension if not given.
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+        /// The minimum width and height in pixels.
+        #[arg(long, default_value_t = 200)]
+        size: u32,
+        /// The error correction level.
+        #[arg(long, value_enum, default_value = "medium")]
+        level: Level,
+        #[command(flatten)]
+        config: Config,
+    },
+}
+
+#[derive(Subcommand)]
+enum UriCommand {
+    /// Build an otpauth:// URI.
+    Build {
+        #[command(flatten)]
+        account: AccountArgs,
+        #[command(flatten)]
+        config: Config,
+    },
+    /// Parse an otpauth:// URI and show its parameters.
+    Parse {
+        /// The URI to parse.
+        uri: String,
+    },
+}
+
+#[derive(Args)]
+struct SecretArg {
+    /// The base32 secret, an otpauth:// URI, or `-` to read it from standard input.
+    secret: String,
+}
+
+#[derive(Args)]
+struct AccountArgs {
+    #[command(flatten)]
+    secret: SecretArg,
+    /// The account name shown in the app.
+    #[arg(long)]
+    account: String,
+    /// The issuer shown in the app.
+    #[arg(long)]
+    issuer: Option<String>,
+    /// The initial counter of a counter based (HOTP) account.
+    #[arg(long)]
+    counter: Option<u64>,
+}
+
+#[derive(Args)]
+struct Config {
+    /// The HMAC algorithm.
+    #[arg(long, value_enum)]
+    algorithm: Option<AlgorithmArg>,
+    /// The number of digits of codes.
+    #[arg(long)]
+    digits: Option<usize>,
+    /// The period of time based codes in seconds.
+    #[arg(long)]
+    period: Option<u64>,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum AlgorithmArg {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Format {
+    Svg,
+    Png,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Level {
+    Low,
+    Medium,
+    Quartile,
+    High,
+}
+
+impl From<AlgorithmArg> for Algorithm {
+    fn from(algorithm: AlgorithmArg) -> Self {
+        match algorithm {
+            AlgorithmArg::Sha1 => Algorithm::Sha1,
+            AlgorithmArg::Sha256 => Algorithm::Sha256,
+            AlgorithmArg::Sha512 => Algorithm::Sha512,
+        }
+    }
+}
+
+impl From<Level> for ErrorCorrectionLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Low => ErrorCorrectionLevel::Low,
+            Level::Medium => ErrorCorrectionLevel::Medium,
+            Level::Quartile => ErrorCorrectionLevel::Quartile,
+            Level::High => ErrorCorrectionLevel::High,
+        }
+    }
+}
+
+impl Config {
+    /// Applies the flags to the parameters of a URI.
+    fn apply(&self, mut uri: OtpAuthUri) -> OtpAuthUri {
+        if let Some(algorithm) = self.algorithm {
+            uri = uri.with_algorithm(algorithm.into());
+        }
+        if let Some(digits) = self.digits {
+            uri = uri.with_digits(digits);
+        }
+        if let Some(period) = self.period {
+            uri = uri.with_period(Duration::from_secs(period));
+        }
+        uri
+    }
+}
+
+impl SecretArg {
+    /// The account of the secret. A base32 secret is returned as an account without a name.
+    fn account(&self, config: &Config) -> Result<OtpAuthUri> {
+        let secret = if self.secret == "-" {
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            line.trim().to_string()
+        } else {
+            self.secret.clone()
+        };
+        let uri = if secret.to_ascii_lowercase().starts_with("otpauth://") {
+            secret.parse()?
+        } else {
+            OtpAuthUri::new(&Secret::normalize(&secret)?, "")
+        };
+        Ok(config.apply(uri))
+    }
+}
+
+impl AccountArgs {
+    fn uri(&self, config: &Config) -> Result<OtpAuthUri> {
+        let mut uri = self.secret.account(config)?;
+        uri.account.clone_from(&self.account);
+        if let Some(issuer) = &self.issuer {
+            uri = uri.with_issuer(issuer);
+        }
+        if let Some(counter) = self.counter {
+            uri = uri.with_counter(counter);
+        }
+        uri.secret = uri.secret.trim_end_matches('=').to_string();
+        uri.validate()?;
+        Ok(uri)
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse(), &mut io::stdout().lock()) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("google-authenticator: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs a command, writing its output to `out`.
+fn run(cli: Cli, out: &mut dyn Write) -> Result<ExitCode> {
+    match cli.command {
+        Command::New {
+            account,
+            issuer,
+            bits,
+            ansi,
+            light_background,
+            config,
+        } => {
+            let secret = Secret::generate(bits)?;
+            let mut uri = config.apply(OtpAuthUri::new(
+                secret.expose_base32().trim_end_matches('='),
+                &account,
+            ));
+            if let Some(issuer) = issuer {
+                uri = uri.with_issuer(&issuer);
+            }
+            uri.validate()?;
+            let uri = uri.to_string();
+            let qr = TerminalQr::new()
+                .with_ansi(ansi)
+                .with_inverted(!ansi && !light_background)
+                .render(&uri, ErrorCorrectionLevel::Medium)?;
+            writeln!(out, "{}", qr)?;
+            writeln!(out, "Secret: {}", secret.expose_base32())?;
+        }
+        Command::Code {
+            secret,
+            time,
+            counter,
+            watch,
+            config,
+        } => {
+            let uri = secret.account(&config)?;
+            let authenticator = authenticator(&uri, time);
+            let counter = counter.or(match uri.otp_type {
+                OtpType::Hotp => Some(uri.counter),
+                OtpType::Totp => None,
+            });
+            if let Some(counter) = counter {
+                writeln!(
+                    out,
+                    "{}",
+                    authenticator.get_hotp_code(&uri.secret, counter)?
+                )?;
+            } else if time.is_some() {
+                writeln!(out, "{}", authenticator.get_code(&uri.secret, 0)?)?;
+            } else {
+                loop {
+                    let now = SystemClock.now();
+                    let code = authenticator.get_code(&uri.secret, 0)?;
+                    let remaining = uri.period - now % uri.period;
+                    if !watch {
+                        writeln!(out, "{} ({}s left)", code, remaining)?;
+                        break;
+                    }
+                    write!(out, "\r{} ({:>3}s left)", code, remaining)?;
+                    out.flush()?;
+                    thread::sleep(Duration::from_secs(1));
+                }
+            }
+        }
+        Command::Verify {
+            secret,
+            code,
+            window,
+            time,
+            config,
+        } => {
+            let uri = secret.account(&config)?;
+            let authenticator = authenticator(&uri, time);
+            let verified = match uri.otp_type {
+                OtpType::Hotp => authenticator
+                    .verify_hotp_code(&uri.secret, &code, uri.counter, window)
+                    .map(|counter| format!("valid, next counter {}", counter))
+                    .ok_or_else(|| "invalid".to_string()),
+                OtpType::Totp => authenticator
+                    .verify_code_detailed(&uri.secret, &code, window, 0, None)
+                    .map(|verification| format!("valid, offset {:+}", verification.offset))
+                    .map_err(|rejection| format!("invalid, {}", rejection)),
+            };
+            return Ok(match verified {
+                Ok(message) => {
+                    writeln!(out, "{}", message)?;
+                    ExitCode::SUCCESS
+                }
+                Err(message) => {
+                    writeln!(out, "{}", message)?;
+                    ExitCode::FAILURE
+                }
+            });
+        }
+        Command::Uri {
+            command: UriCommand::Build { account, config },
+        } => writeln!(out, "{}", account.uri(&config)?)?,
+        Command::Uri {
+            command: UriCommand::Parse { uri },
+        } => {
+            let uri: OtpAuthUri = uri.parse()?;
+            writeln!(out, "type:      {:?}", uri.otp_type)?;
+            writeln!(out, "secret:    {}", uri.secret)?;
+            writeln!(out, "account:   {}", uri.account)?;
+            writeln!(out, "issuer:    {}", uri.issuer.as_deref().unwrap_or(""))?;
+            writeln!(out, "algorithm: {}", uri.algorithm)?;
+            writeln!(out, "digits:    {}", uri.digits)?;
+            match uri.otp_type {
+                OtpType::Totp => writeln!(out, "period:    {}", uri.period)?,
+                OtpType::Hotp => writeln!(out, "counter:   {}", uri.counter)?,
+            }
+            for (key, value) in &uri.parameters {
+                writeln!(out, "{}: {}", key, value)?;
+            }
+        }
+        Command::Qr {
+            account,
+            output,
+            format,
+            size,
+            level,
+            config,
+        } => {
+            let uri = account.uri(&config)?;
+            let format = match format {
+                Some(format) => format,
+                None => match output.extension().and_then(|e| e.to_str()) {
+                    Some(e) if e.eq_ignore_ascii_case("png") => Format::Png,
+                    Some(e) if e.eq_ignore_ascii_case("svg") => Format::Svg,
+                    _ => return Err(GAError::Error("unknown format. use --format svg or png")),
+                },
+            };
+            match format {
+                Format::Svg => std::fs::write(&output, uri.qr_code(size, size, level.into())?)?,
+                Format::Png => std::fs::write(&output, uri.qr_code_png(size, size, level.into())?)?,
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The authenticator of an account, at the unix time `time` if given.
+fn authenticator(uri: &OtpAuthUri, time: Option<u64>) -> GoogleAuthenticator {
+    match time {
+        Some(time) => uri.authenticator().with_clock(FixedClock::new(time)),
+        None => uri.authenticator(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    /// Parses and runs a command line, returning its exit code and output.
+    fn run_args(args: &[&str]) -> Result<(ExitCode, String)> {
+        let cli = Cli::try_parse_from(["google-authenticator"].iter().chain(args)).unwrap();
+        let mut out = Vec::new();
+        let code = run(cli, &mut out)?;
+        Ok((code, String::from_utf8(out).unwrap()))
+    }
+
+    #[test]
+    fn new() {
+        let (code, out) = run_args(&["new", "--account", "alice", "--issuer", "ACME"]).unwrap();
+        assert_eq!(ExitCode::SUCCESS, code);
+        let uri = out
+            .lines()
+            .find(|line| line.starts_with("otpauth://"))
+            .unwrap();
+        assert!(uri.starts_with("otpauth://totp/ACME:alice?secret="));
+        let uri: OtpAuthUri = uri.parse().unwrap();
+        assert_eq!(Some("ACME".to_string()), uri.issuer);
+
+        let (_, out) = run_args(&["new", "--digits", "8", "--period", "60"]).unwrap();
+        assert!(out.contains("&digits=8&period=60"));
+
+        assert!(run_args(&["new", "--digits", "4"]).is_err());
+        assert!(run_args(&["new", "--period", "0"]).is_err());
+    }
+
+    #[test]
+    fn code_time() {
+        let (code, out) = run_args(&["code", SECRET, "--time", "59"]).unwrap();
+        assert_eq!(ExitCode::SUCCESS, code);
+        assert_eq!("287082\n", out);
+
+        let (_, out) = run_args(&["code", SECRET, "--time", "59", "--digits", "8"]).unwrap();
+        assert_eq!("94287082\n", out);
+        let (_, out) = run_args(&["code", SECRET, "--counter", "1"]).unwrap();
+        assert_eq!("287082\n", out);
+    }
+
+    #[test]
+    fn verify_time() {
+        let (code, out) = run_args(&["verify", SECRET, "287082", "--time", "59"]).unwrap();
+        assert_eq!(ExitCode::SUCCESS, code);
+        assert_eq!("valid, offset +0\n", out);
+
+        let (code, out) = run_args(&["verify", SECRET, "287082", "--time", "89"]).unwrap();
+        assert_eq!(ExitCode::SUCCESS, code);
+        assert_eq!("valid, offset -1\n", out);
+
+        let (code, out) = run_args(&["verify", SECRET, "287082", "--time", "1000"]).unwrap();
+        assert_eq!(ExitCode::FAILURE, code);
+        assert!(out.starts_with("invalid"));
+    }
+
+    #[test]
+    fn uri_build() {
+        let (code, out) = run_args(&[
+            "uri",
+            "build",
+            SECRET,
+            "--account",
+            "alice",
+            "--issuer",
+            "ACME Co",
+            "--algorithm",
+            "sha256",
+        ])
+        .unwrap();
+        assert_eq!(ExitCode::SUCCESS, code);
+        assert_eq!(
+            format!(
+                "otpauth://totp/ACME%20Co:alice?secret={}&issuer=ACME%20Co&algorithm=SHA256&digits=6&period=30\n",
+                SECRET
+            ),
+            out
+        );
+
+        let (_, out) = run_args(&["uri", "build", SECRET, "--account", "a:b"]).unwrap();
+        let uri: OtpAuthUri = out.trim().parse().unwrap();
+        assert_eq!("a:b", uri.account);
+
+        let build = ["uri", "build", SECRET, "--account", "alice"];
+        assert!(run_args(&[&build[..], &["--digits", "11"]].concat()).is_err());
+        assert!(run_args(&[&build[..], &["--period", "0"]].concat()).is_err());
+        assert!(run_args(&["uri", "build", "not base32!", "--account", "alice"]).is_err());
+    }
+
+    #[test]
+    fn uri_parse() {
+        let (code, out) = run_args(&[
+            "uri",
+            "parse",
+            "otpauth://hotp/ACME:alice?secret=JBSWY3DPEHPK3PXP&issuer=ACME&counter=7&image=logo",
+        ])
+        .unwrap();
+        assert_eq!(ExitCode::SUCCESS, code);
+        assert_eq!(
+            "type:      Hotp\n\
+             secret:    JBSWY3DPEHPK3PXP\n\
+             account:   alice\n\
+             issuer:    ACME\n\
+             algorithm: SHA1\n\
+             digits:    6\n\
+             counter:   7\n\
+             image: logo\n",
+            out
+        );
+        assert!(run_args(&["uri", "parse", "otpauth://totp/alice"]).is_err());
+    }
+}
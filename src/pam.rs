@@ -0,0 +1,486 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A PAM service module that verifies codes against the `~/.google_authenticator` file of the
+//! user, built with the feature flag `pam`. The module understands these arguments:
+//!
+//! - `secret=<path>`: the path of the file, default `~/.google_authenticator`. `~` and `${HOME}`
+//!   are replaced with the home directory of the user, and `${USER}` with its name.
+//! - `nullok`: ignore the module for users without a file, instead of failing.
+//! - `window_size=<n>`: the number of accepted codes, which overrides `" WINDOW_SIZE`.
+//! - `forward_pass`: ask for the password and the code in a single prompt, and pass the
+//!   password on to the next module.
+//!
+//! The file is only accessed with the file system permissions of the user, and only if it is a
+//! regular file that is owned by the user and not accessible by others.
+
+use crate::{AuthenticatorFile, Clock, SystemClock};
+use libc::{c_char, c_int, c_void};
+use std::ffi::{CStr, CString};
+use std::fs::OpenOptions;
+use std::io::{self, Read};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::{mem, ptr};
+use zeroize::Zeroize;
+
+/// cbindgen:ignore
+const PAM_SUCCESS: c_int = 0;
+/// cbindgen:ignore
+const PAM_SERVICE_ERR: c_int = 3;
+/// cbindgen:ignore
+const PAM_AUTH_ERR: c_int = 7;
+/// cbindgen:ignore
+const PAM_AUTHINFO_UNAVAIL: c_int = 9;
+/// cbindgen:ignore
+const PAM_USER_UNKNOWN: c_int = 10;
+/// cbindgen:ignore
+const PAM_CONV_ERR: c_int = 19;
+/// cbindgen:ignore
+const PAM_IGNORE: c_int = 25;
+/// cbindgen:ignore
+const PAM_CONV: c_int = 5;
+/// cbindgen:ignore
+const PAM_AUTHTOK: c_int = 6;
+/// cbindgen:ignore
+const PAM_PROMPT_ECHO_OFF: c_int = 1;
+/// cbindgen:ignore
+const DEFAULT_SECRET_PATH: &str = "~/.google_authenticator";
+
+/// The opaque `pam_handle_t` of libpam.
+#[repr(C)]
+pub struct PamHandle {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct PamMessage {
+    msg_style: c_int,
+    msg: *const c_char,
+}
+
+#[repr(C)]
+struct PamResponse {
+    resp: *mut c_char,
+    resp_retcode: c_int,
+}
+
+#[repr(C)]
+struct PamConv {
+    conv: Option<
+        unsafe extern "C" fn(
+            c_int,
+            *mut *const PamMessage,
+            *mut *mut PamResponse,
+            *mut c_void,
+        ) -> c_int,
+    >,
+    appdata_ptr: *mut c_void,
+}
+
+extern "C" {
+    fn pam_get_user(pamh: *mut PamHandle, user: *mut *const c_char, prompt: *const c_char)
+        -> c_int;
+    fn pam_get_item(pamh: *const PamHandle, item_type: c_int, item: *mut *const c_void) -> c_int;
+    fn pam_set_item(pamh: *mut PamHandle, item_type: c_int, item: *const c_void) -> c_int;
+}
+
+/// The arguments of the module in the PAM configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PamOptions {
+    pub(crate) secret: String,
+    pub(crate) nullok: bool,
+    pub(crate) window_size: Option<u64>,
+    pub(crate) forward_pass: bool,
+}
+
+impl PamOptions {
+    pub(crate) fn parse<'a, I: IntoIterator<Item = &'a str>>(args: I) -> Option<Self> {
+        let mut options = PamOptions {
+            secret: DEFAULT_SECRET_PATH.to_string(),
+            nullok: false,
+            window_size: None,
+            forward_pass: false,
+        };
+        for arg in args {
+            if let Some(secret) = arg.strip_prefix("secret=") {
+                options.secret = secret.to_string();
+            } else if let Some(window_size) = arg.strip_prefix("window_size=") {
+                options.window_size = Some(window_size.parse().ok().filter(|w| *w > 0)?);
+            } else if arg == "nullok" {
+                options.nullok = true;
+            } else if arg == "forward_pass" {
+                options.forward_pass = true;
+            } else {
+                return None;
+            }
+        }
+        Some(options)
+    }
+
+    /// The path of the file of a user, with `~`, `${HOME}` and `${USER}` replaced.
+    pub(crate) fn secret_path(&self, user: &str, home: &str) -> PathBuf {
+        let secret = match self.secret.strip_prefix('~') {
+            Some(rest) => format!("{}{}", home, rest),
+            None => self.secret.clone(),
+        };
+        PathBuf::from(secret.replace("${HOME}", home).replace("${USER}", user))
+    }
+}
+
+/// Splits the input of the combined prompt of `forward_pass` into the password and the code.
+/// The code is the last 6 digits, or the last 8 digits if they are a scratch code.
+pub(crate) fn split_code<'a>(
+    input: &'a str,
+    file: &AuthenticatorFile,
+) -> Option<(&'a str, &'a str)> {
+    let scratch_code = input
+        .len()
+        .checked_sub(8)
+        .and_then(|i| input.get(i..).map(|code| (i, code)))
+        .filter(|(_, code)| file.scratch_codes.iter().any(|c| c == code));
+    let split = match scratch_code {
+        Some((i, _)) => i,
+        None => input.len().checked_sub(6)?,
+    };
+    let (password, code) = (input.get(..split)?, input.get(split..)?);
+    if code.bytes().all(|b| b.is_ascii_digit()) {
+        Some((password, code))
+    } else {
+        None
+    }
+}
+
+/// Authenticates the user with a code. See the documentation of the module for its arguments.
+///
+/// # Safety
+/// This function must only be called by libpam, with a valid handle and arguments.
+#[no_mangle]
+pub unsafe extern "C" fn pam_sm_authenticate(
+    pamh: *mut PamHandle,
+    _flags: c_int,
+    argc: c_int,
+    argv: *const *const c_char,
+) -> c_int {
+    let args = (0..argc.max(0) as usize)
+        .map(|i| unsafe { CStr::from_ptr(*argv.add(i)) }.to_str().ok())
+        .collect::<Option<Vec<_>>>();
+    let Some(options) = args.and_then(PamOptions::parse) else {
+        return PAM_SERVICE_ERR;
+    };
+    let mut user = ptr::null();
+    if unsafe { pam_get_user(pamh, &mut user, ptr::null()) } != PAM_SUCCESS || user.is_null() {
+        return PAM_USER_UNKNOWN;
+    }
+    let Ok(user) = unsafe { CStr::from_ptr(user) }.to_str() else {
+        return PAM_USER_UNKNOWN;
+    };
+    let Some((uid, gid, home)) = passwd(user) else {
+        return PAM_USER_UNKNOWN;
+    };
+    let path = options.secret_path(user, &home);
+
+    let mut file = {
+        // Like libpam-google-authenticator, files in the home directory of the user are only
+        // accessed with the permissions of the user.
+        let Some(_privileges) = UserPrivileges::switch(uid, gid) else {
+            return PAM_AUTHINFO_UNAVAIL;
+        };
+        match load(&path, uid) {
+            Ok(file) => file,
+            Err(error) if options.nullok && error.kind() == io::ErrorKind::NotFound => {
+                return PAM_IGNORE
+            }
+            Err(_) => return PAM_AUTHINFO_UNAVAIL,
+        }
+    };
+
+    let prompt = if options.forward_pass {
+        "Password & verification code: "
+    } else {
+        "Verification code: "
+    };
+    let Some(mut input) = (unsafe { converse(pamh, prompt) }) else {
+        return PAM_CONV_ERR;
+    };
+    let code = if options.forward_pass {
+        match split_code(&input, &file) {
+            Some((password, code)) => {
+                let Ok(password) = CString::new(password) else {
+                    input.zeroize();
+                    return PAM_AUTH_ERR;
+                };
+                let result =
+                    unsafe { pam_set_item(pamh, PAM_AUTHTOK, password.as_ptr() as *const c_void) };
+                // pam_set_item copies the password.
+                password.into_bytes().zeroize();
+                if result != PAM_SUCCESS {
+                    input.zeroize();
+                    return PAM_SERVICE_ERR;
+                }
+                code.to_string()
+            }
+            None => String::new(),
+        }
+    } else {
+        input.clone()
+    };
+    input.zeroize();
+
+    let original = file.clone();
+    file.window_size = options.window_size.or(file.window_size);
+    let verified = file.verify(&code, SystemClock.now());
+    file.window_size = original.window_size;
+    if file != original {
+        let Some(_privileges) = UserPrivileges::switch(uid, gid) else {
+            return PAM_AUTHINFO_UNAVAIL;
+        };
+        if file.save(&path).is_err() {
+            return PAM_AUTHINFO_UNAVAIL;
+        }
+    }
+    match verified {
+        Ok(true) => PAM_SUCCESS,
+        Ok(false) => PAM_AUTH_ERR,
+        Err(_) => PAM_AUTHINFO_UNAVAIL,
+    }
+}
+
+/// Credentials are not used by this module.
+///
+/// # Safety
+/// This function must only be called by libpam.
+#[no_mangle]
+pub unsafe extern "C" fn pam_sm_setcred(
+    _pamh: *mut PamHandle,
+    _flags: c_int,
+    _argc: c_int,
+    _argv: *const *const c_char,
+) -> c_int {
+    PAM_SUCCESS
+}
+
+/// Account management is not provided by this module.
+///
+/// # Safety
+/// This function must only be called by libpam.
+#[no_mangle]
+pub unsafe extern "C" fn pam_sm_acct_mgmt(
+    _pamh: *mut PamHandle,
+    _flags: c_int,
+    _argc: c_int,
+    _argv: *const *const c_char,
+) -> c_int {
+    PAM_IGNORE
+}
+
+/// Sessions are not managed by this module.
+///
+/// # Safety
+/// This function must only be called by libpam.
+#[no_mangle]
+pub unsafe extern "C" fn pam_sm_open_session(
+    _pamh: *mut PamHandle,
+    _flags: c_int,
+    _argc: c_int,
+    _argv: *const *const c_char,
+) -> c_int {
+    PAM_IGNORE
+}
+
+/// Sessions are not managed by this module.
+///
+/// # Safety
+/// This function must only be called by libpam.
+#[no_mangle]
+pub unsafe extern "C" fn pam_sm_close_session(
+    _pamh: *mut PamHandle,
+    _flags: c_int,
+    _argc: c_int,
+    _argv: *const *const c_char,
+) -> c_int {
+    PAM_IGNORE
+}
+
+/// Passwords are not changed by this module.
+///
+/// # Safety
+/// This function must only be called by libpam.
+#[no_mangle]
+pub unsafe extern "C" fn pam_sm_chauthtok(
+    _pamh: *mut PamHandle,
+    _flags: c_int,
+    _argc: c_int,
+    _argv: *const *const c_char,
+) -> c_int {
+    PAM_IGNORE
+}
+
+/// Asks the user for input without echo, using the conversation function of the application.
+unsafe fn converse(pamh: *mut PamHandle, prompt: &str) -> Option<String> {
+    let mut conv: *const c_void = ptr::null();
+    if unsafe { pam_get_item(pamh, PAM_CONV, &mut conv) } != PAM_SUCCESS || conv.is_null() {
+        return None;
+    }
+    let conv = unsafe { &*(conv as *const PamConv) };
+    let prompt = CString::new(prompt).ok()?;
+    let message = PamMessage {
+        msg_style: PAM_PROMPT_ECHO_OFF,
+        msg: prompt.as_ptr(),
+    };
+    let mut messages = [&message as *const PamMessage];
+    let mut response: *mut PamResponse = ptr::null_mut();
+    let result = unsafe { (conv.conv?)(1, messages.as_mut_ptr(), &mut response, conv.appdata_ptr) };
+    if response.is_null() {
+        return None;
+    }
+    // The application allocates the response with malloc, and the module has to free it.
+    let resp = unsafe { (*response).resp };
+    let input = if result == PAM_SUCCESS && !resp.is_null() {
+        unsafe { CStr::from_ptr(resp) }
+            .to_str()
+            .ok()
+            .map(str::to_string)
+    } else {
+        None
+    };
+    unsafe {
+        if !resp.is_null() {
+            let len = libc::strlen(resp);
+            ptr::write_bytes(resp, 0, len);
+            libc::free(resp as *mut c_void);
+        }
+        libc::free(response as *mut c_void);
+    }
+    input
+}
+
+/// The uid and home directory of a user.
+fn passwd(user: &str) -> Option<(libc::uid_t, libc::gid_t, String)> {
+    let user = CString::new(user).ok()?;
+    let mut buf = vec![0 as c_char; 16384];
+    let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut result = ptr::null_mut();
+    let err = unsafe {
+        libc::getpwnam_r(
+            user.as_ptr(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if err != 0 || result.is_null() || pwd.pw_dir.is_null() {
+        return None;
+    }
+    let home = unsafe { CStr::from_ptr(pwd.pw_dir) }.to_str().ok()?;
+    Some((pwd.pw_uid, pwd.pw_gid, home.to_string()))
+}
+
+/// Opens the file at `path` without following symbolic links, and parses it if it is a regular
+/// file of the user that is not accessible by others. The checked and the parsed file are the
+/// same, even if the path is replaced in between.
+fn load(path: &Path, uid: libc::uid_t) -> io::Result<AuthenticatorFile> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW | libc::O_NOCTTY)
+        .open(path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "the file must be private to the user",
+        ));
+    }
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let parsed = contents
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad file"));
+    contents.zeroize();
+    parsed
+}
+
+/// Switches to a user and group for file system accesses, until it is dropped. On Linux, only
+/// the file system user and group are changed, elsewhere the effective ones.
+struct UserPrivileges {
+    uid: libc::uid_t,
+    gid: libc::gid_t,
+}
+
+impl UserPrivileges {
+    #[cfg(target_os = "linux")]
+    fn switch(uid: libc::uid_t, gid: libc::gid_t) -> Option<Self> {
+        // setfsuid returns the previous id, so calling it again checks whether it changed.
+        let old_gid = unsafe { libc::setfsgid(gid) } as libc::gid_t;
+        if unsafe { libc::setfsgid(gid) } as libc::gid_t != gid {
+            unsafe { libc::setfsgid(old_gid) };
+            return None;
+        }
+        let old_uid = unsafe { libc::setfsuid(uid) } as libc::uid_t;
+        if unsafe { libc::setfsuid(uid) } as libc::uid_t != uid {
+            unsafe {
+                libc::setfsuid(old_uid);
+                libc::setfsgid(old_gid);
+            }
+            return None;
+        }
+        Some(UserPrivileges {
+            uid: old_uid,
+            gid: old_gid,
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn switch(uid: libc::uid_t, gid: libc::gid_t) -> Option<Self> {
+        let (old_uid, old_gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        if unsafe { libc::setegid(gid) } != 0 {
+            return None;
+        }
+        if unsafe { libc::seteuid(uid) } != 0 {
+            unsafe { libc::setegid(old_gid) };
+            return None;
+        }
+        Some(UserPrivileges {
+            uid: old_uid,
+            gid: old_gid,
+        })
+    }
+}
+
+impl Drop for UserPrivileges {
+    #[cfg(target_os = "linux")]
+    fn drop(&mut self) {
+        unsafe {
+            libc::setfsuid(self.uid);
+            libc::setfsgid(self.gid);
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn drop(&mut self) {
+        unsafe {
+            libc::seteuid(self.uid);
+            libc::setegid(self.gid);
+        }
+    }
+}
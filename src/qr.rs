@@ -0,0 +1,578 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{ErrorCorrectionLevel, GAError, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use qrcode::{Color as Module, QrCode};
+use std::fmt;
+use std::io::Cursor;
+use std::str::FromStr;
+
+/// A colour of a rendered QR code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Color {
+    /// The red component.
+    pub r: u8,
+    /// The green component.
+    pub g: u8,
+    /// The blue component.
+    pub b: u8,
+    /// The opacity, 255 is opaque.
+    pub a: u8,
+}
+
+impl Color {
+    /// Black, the default colour of dark modules.
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    /// White, the default colour of light modules and the quiet zone.
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+
+    /// Create an opaque colour.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 255 }
+    }
+
+    /// Create a colour with an opacity.
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+}
+
+/// Parses colours written like `#rrggbb` or `#rrggbbaa`.
+impl FromStr for Color {
+    type Err = GAError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 || hex.len() == 8)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok().map(|v| (hex.len(), v)));
+        match hex {
+            Some((6, v)) => Ok(Color::rgb((v >> 16) as u8, (v >> 8) as u8, v as u8)),
+            Some((_, v)) => Ok(Color::rgba(
+                (v >> 24) as u8,
+                (v >> 16) as u8,
+                (v >> 8) as u8,
+                v as u8,
+            )),
+            None => Err(GAError::Error(
+                "color must be written as #rrggbb or #rrggbbaa",
+            )),
+        }
+    }
+}
+
+/// Writes colours like `#rrggbb`, or `#rrggbbaa` if they are not opaque.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+/// A logo drawn in the centre of a QR code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Logo {
+    /// An SVG document. It can only be drawn on QR codes rendered as SVG.
+    Svg(String),
+    /// A PNG image.
+    Png(Vec<u8>),
+}
+
+impl Logo {
+    fn data_url(&self) -> String {
+        match self {
+            Logo::Svg(svg) => format!("data:image/svg+xml;base64,{}", STANDARD.encode(svg)),
+            Logo::Png(png) => format!("data:image/png;base64,{}", STANDARD.encode(png)),
+        }
+    }
+}
+
+/// The largest logo, as a percentage of the width of the QR code, that can be recovered by the
+/// error correction.
+/// cbindgen:ignore
+const LOGO_MAX_SIZE: u32 = 30;
+
+/// The radius of the corners of rounded modules, relative to the module size.
+/// cbindgen:ignore
+const MODULE_RADIUS: f32 = 0.3;
+
+/// The colours, quiet zone, module shape, logo and module size of rendered QR codes. The defaults
+/// are square black modules on white, with the quiet zone of 4 modules required by the QR code
+/// specification.
+///
+/// ### Example
+/// ```rust
+/// use google_authenticator::{Color, ErrorCorrectionLevel, GoogleAuthenticator, Logo, QrStyle};
+///
+/// let uri = GoogleAuthenticator::new().otp_auth_uri("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", "alice", "ACME");
+/// let style = QrStyle::new()
+///     .with_dark_color(Color::rgb(0x1a, 0x23, 0x7e))
+///     .with_quiet_zone(2)
+///     .with_module_size(8);
+/// let png = style
+///     .png(&uri.to_string(), 0, 0, ErrorCorrectionLevel::Medium)
+///     .unwrap();
+/// assert!(png.starts_with(b"\x89PNG"));
+///
+/// let logo = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 2 2"><circle cx="1" cy="1" r="1"/></svg>"#;
+/// let svg = style
+///     .with_rounded_modules(true)
+///     .with_logo(Logo::Svg(logo.to_string()))
+///     .svg(&uri.to_string(), 0, 0, ErrorCorrectionLevel::Medium)
+///     .unwrap();
+/// assert!(svg.contains("data:image/svg+xml;base64,"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QrStyle {
+    dark_color: Color,
+    light_color: Color,
+    quiet_zone: u32,
+    module_size: Option<u32>,
+    rounded: bool,
+    logo: Option<Logo>,
+    logo_size: u32,
+}
+
+impl Default for QrStyle {
+    fn default() -> Self {
+        QrStyle {
+            dark_color: Color::BLACK,
+            light_color: Color::WHITE,
+            quiet_zone: 4,
+            module_size: None,
+            rounded: false,
+            logo: None,
+            logo_size: 20,
+        }
+    }
+}
+
+impl QrStyle {
+    /// Create the default style.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the colour of dark modules.
+    pub fn with_dark_color(mut self, color: Color) -> Self {
+        self.dark_color = color;
+        self
+    }
+
+    /// Set the colour of light modules, of the quiet zone and behind the logo.
+    pub fn with_light_color(mut self, color: Color) -> Self {
+        self.light_color = color;
+        self
+    }
+
+    /// Set the width of the quiet zone around the QR code, in modules. Some scanners need at
+    /// least 4 modules.
+    pub fn with_quiet_zone(mut self, modules: u32) -> Self {
+        self.quiet_zone = modules;
+        self
+    }
+
+    /// Set the size of each module in pixels. If it is set, the width and height passed when
+    /// rendering are ignored, otherwise the smallest module size that reaches them is used.
+    pub fn with_module_size(mut self, pixels: u32) -> Self {
+        self.module_size = Some(pixels);
+        self
+    }
+
+    /// Draw dark modules as squares with rounded corners.
+    pub fn with_rounded_modules(mut self, rounded: bool) -> Self {
+        self.rounded = rounded;
+        self
+    }
+
+    /// Draw a logo in the centre of the QR code, on the light colour. The modules behind it are
+    /// restored by the error correction, so the level is raised to `High` when a logo is set.
+    pub fn with_logo(mut self, logo: Logo) -> Self {
+        self.logo = Some(logo);
+        self
+    }
+
+    /// Set the width of the logo, as a percentage of the width of the QR code without its quiet
+    /// zone. The default is 20, and larger logos than 30 are rejected when rendering because the
+    /// code would no longer scan.
+    pub fn with_logo_size(mut self, percent: u32) -> Self {
+        self.logo_size = percent;
+        self
+    }
+
+    /// Renders `data`, usually an `otpauth://` URI, as a PNG image. The `height` and `width`
+    /// parameters are the minimum dimensions of the image, or the default of `200px` if they are
+    /// 0, unless a module size is set. SVG logos can't be drawn on PNG images.
+    pub fn png(
+        &self,
+        data: &str,
+        width: u32,
+        height: u32,
+        level: ErrorCorrectionLevel,
+    ) -> Result<Vec<u8>> {
+        let code = self.code(data, level)?;
+        let mut image = self.raster(&code, width, height);
+        if let Some(logo) = &self.logo {
+            let logo = match logo {
+                Logo::Png(png) => image::load_from_memory_with_format(png, ImageFormat::Png)
+                    .map_err(|_| GAError::Error("can't decode png logo"))?,
+                Logo::Svg(_) => {
+                    return Err(GAError::Error("svg logos can only be drawn on svg images"))
+                }
+            };
+            let (module_size, _) = self.dimensions(&code, width, height);
+            let (offset, side) = self.logo_area(&code);
+            let logo = logo
+                .resize(side * module_size, side * module_size, FilterType::Triangle)
+                .to_rgba8();
+            let x = offset * module_size + (side * module_size - logo.width()) / 2;
+            let y = offset * module_size + (side * module_size - logo.height()) / 2;
+            imageops::overlay(&mut image, &logo, i64::from(x), i64::from(y));
+        }
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut png, ImageFormat::Png)
+            .map_err(|_| GAError::Error("can't encode png"))?;
+        Ok(png.into_inner())
+    }
+
+    /// Renders `data`, usually an `otpauth://` URI, as an SVG file. The dimensions are the same as
+    /// for `png`.
+    pub fn svg(
+        &self,
+        data: &str,
+        width: u32,
+        height: u32,
+        level: ErrorCorrectionLevel,
+    ) -> Result<String> {
+        let code = self.code(data, level)?;
+        let (module_size, size) = self.dimensions(&code, width, height);
+        let modules = size / module_size;
+        let code_width = code.width() as u32;
+        let mut svg = format!(
+            concat!(
+                r#"<?xml version="1.0" standalone="yes"?>"#,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{size}" height="{size}" viewBox="0 0 {modules} {modules}">"#,
+                r#"<rect width="{modules}" height="{modules}" {light}/><path {dark}{crisp} d=""#,
+            ),
+            size = size,
+            modules = modules,
+            light = fill(self.light_color),
+            dark = fill(self.dark_color),
+            crisp = if self.rounded {
+                ""
+            } else {
+                r#" shape-rendering="crispEdges""#
+            },
+        );
+        for (i, module) in code.to_colors().into_iter().enumerate() {
+            if module != Module::Dark {
+                continue;
+            }
+            let x = i as u32 % code_width + self.quiet_zone;
+            let y = i as u32 / code_width + self.quiet_zone;
+            if self.rounded {
+                // A unit square with corners of radius MODULE_RADIUS.
+                svg.push_str(&format!(
+                    "M{x}.3 {y}h.4a.3 .3 0 0 1 .3 .3v.4a.3 .3 0 0 1-.3 .3h-.4a.3 .3 0 0 1-.3-.3v-.4a.3 .3 0 0 1 .3-.3z",
+                    x = x,
+                    y = y
+                ));
+            } else {
+                svg.push_str(&format!("M{} {}h1v1h-1z", x, y));
+            }
+        }
+        svg.push_str(r#""/>"#);
+        if let Some(logo) = &self.logo {
+            let (offset, side) = self.logo_area(&code);
+            svg.push_str(&format!(
+                r#"<rect x="{o}" y="{o}" width="{s}" height="{s}" {light}/><image x="{o}" y="{o}" width="{s}" height="{s}" href="{url}"/>"#,
+                o = offset,
+                s = side,
+                light = fill(self.light_color),
+                url = logo.data_url(),
+            ));
+        }
+        svg.push_str("</svg>");
+        Ok(svg)
+    }
+
+    /// Encodes `data`, with the error correction level raised for a logo.
+    fn code(&self, data: &str, level: ErrorCorrectionLevel) -> Result<QrCode> {
+        let level = match self.logo {
+            Some(_) if self.logo_size > LOGO_MAX_SIZE => {
+                return Err(GAError::Error(
+                    "bad logo size. must be at most 30 percent of the width",
+                ))
+            }
+            Some(_) => ErrorCorrectionLevel::High,
+            None => level,
+        };
+        Ok(QrCode::with_error_correction_level(
+            data.as_bytes(),
+            level.into(),
+        )?)
+    }
+
+    pub(crate) fn raster(&self, code: &QrCode, width: u32, height: u32) -> RgbaImage {
+        let (module_size, size) = self.dimensions(code, width, height);
+        let modules = code.to_colors();
+        let code_width = code.width() as u32;
+        let (logo_offset, logo_side) = match self.logo {
+            Some(_) => self.logo_area(code),
+            None => (0, 0),
+        };
+        let behind_logo = |x: u32, y: u32| {
+            (logo_offset..logo_offset + logo_side).contains(&x)
+                && (logo_offset..logo_offset + logo_side).contains(&y)
+        };
+        let dark = Rgba([
+            self.dark_color.r,
+            self.dark_color.g,
+            self.dark_color.b,
+            self.dark_color.a,
+        ]);
+        let light = Rgba([
+            self.light_color.r,
+            self.light_color.g,
+            self.light_color.b,
+            self.light_color.a,
+        ]);
+        RgbaImage::from_fn(size, size, |px, py| {
+            let (mx, my) = (px / module_size, py / module_size);
+            if behind_logo(mx, my) {
+                return light;
+            }
+            let x = mx.checked_sub(self.quiet_zone);
+            let y = my.checked_sub(self.quiet_zone);
+            match (x, y) {
+                (Some(x), Some(y)) if x < code_width && y < code_width => {
+                    match modules[(y * code_width + x) as usize] {
+                        Module::Dark if self.inside_module(px, py, module_size) => dark,
+                        _ => light,
+                    }
+                }
+                _ => light,
+            }
+        })
+    }
+
+    /// Whether the pixel is inside its module, which is only false near the corners of rounded
+    /// modules.
+    fn inside_module(&self, px: u32, py: u32, module_size: u32) -> bool {
+        if !self.rounded {
+            return true;
+        }
+        let size = module_size as f32;
+        let radius = MODULE_RADIUS * size;
+        // The distance of the pixel centre to the square whose corners are the circle centres.
+        let distance = |p: u32| {
+            let p = (p % module_size) as f32 + 0.5;
+            (radius - p).max(p - (size - radius)).max(0.0)
+        };
+        distance(px).powi(2) + distance(py).powi(2) <= radius * radius
+    }
+
+    /// The offset and size of the area behind the logo, in modules. The area has the same parity
+    /// as the QR code, so it is centred on whole modules.
+    fn logo_area(&self, code: &QrCode) -> (u32, u32) {
+        let code_width = code.width() as u32;
+        let mut side = (code_width * self.logo_size / 100).max(1);
+        if (code_width - side) % 2 == 1 {
+            side += 1;
+        }
+        (self.quiet_zone + (code_width - side) / 2, side)
+    }
+
+    /// The size of a module and of the whole image in pixels.
+    pub(crate) fn dimensions(&self, code: &QrCode, width: u32, height: u32) -> (u32, u32) {
+        let width = if width == 0 { 200 } else { width };
+        let height = if height == 0 { 200 } else { height };
+        let modules = code.width() as u32 + 2 * self.quiet_zone;
+        let module_size = self
+            .module_size
+            .unwrap_or_else(|| width.max(height).div_ceil(modules))
+            .max(1);
+        (module_size, modules * module_size)
+    }
+}
+
+/// The `fill` attributes of an SVG element with the colour.
+fn fill(color: Color) -> String {
+    let mut fill = format!(r##"fill="#{:02x}{:02x}{:02x}""##, color.r, color.g, color.b);
+    if color.a != 255 {
+        fill.push_str(&format!(r#" fill-opacity="{:.3}""#, color.a as f32 / 255.0));
+    }
+    fill
+}
+
+/// Renders QR codes as text for terminals, for example to enroll users over SSH. By default,
+/// dark modules are drawn with Unicode half blocks, two rows of modules per line, which suits
+/// terminals with a light background, and the encoded URI is printed below the code for users
+/// who can't scan it.
+///
+/// ### Example
+/// ```rust
+/// use google_authenticator::{ErrorCorrectionLevel, GoogleAuthenticator, TerminalQr};
+///
+/// let uri = GoogleAuthenticator::new().otp_auth_uri("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", "alice", "ACME");
+/// // Terminals with a dark background draw the light modules instead.
+/// let text = TerminalQr::new()
+///     .with_inverted(true)
+///     .render(&uri.to_string(), ErrorCorrectionLevel::Medium)
+///     .unwrap();
+/// println!("{}", text);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TerminalQr {
+    ansi: bool,
+    inverted: bool,
+    compact: bool,
+    quiet_zone: u32,
+    uri: bool,
+}
+
+impl Default for TerminalQr {
+    fn default() -> Self {
+        TerminalQr {
+            ansi: false,
+            inverted: false,
+            compact: true,
+            quiet_zone: 2,
+            uri: true,
+        }
+    }
+}
+
+impl TerminalQr {
+    /// Create the default terminal renderer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Paint the modules black and white with ANSI colour escape codes, which works on any
+    /// terminal background.
+    pub fn with_ansi(mut self, ansi: bool) -> Self {
+        self.ansi = ansi;
+        self
+    }
+
+    /// Draw the light modules instead of the dark ones, for terminals with a dark background.
+    /// With ANSI colours, the colours are swapped.
+    pub fn with_inverted(mut self, inverted: bool) -> Self {
+        self.inverted = inverted;
+        self
+    }
+
+    /// Draw two rows of modules per line with half blocks, which is the default. Otherwise each
+    /// module is drawn with two full blocks, which takes four times the space.
+    pub fn with_compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Set the width of the quiet zone around the QR code, in modules.
+    pub fn with_quiet_zone(mut self, modules: u32) -> Self {
+        self.quiet_zone = modules;
+        self
+    }
+
+    /// Print the rendered data, usually the `otpauth://` URI, on a line below the QR code, which
+    /// is the default.
+    pub fn with_uri(mut self, uri: bool) -> Self {
+        self.uri = uri;
+        self
+    }
+
+    /// Renders `data`, usually an `otpauth://` URI, as lines of text.
+    pub fn render(&self, data: &str, level: ErrorCorrectionLevel) -> Result<String> {
+        let code = QrCode::with_error_correction_level(data.as_bytes(), level.into())?;
+        let code_width = code.width() as u32;
+        let modules = code.to_colors();
+        let size = code_width + 2 * self.quiet_zone;
+        // Whether a module is drawn, which includes the quiet zone and rows past the bottom.
+        let filled = |x: u32, y: u32| {
+            let dark = match (
+                x.checked_sub(self.quiet_zone),
+                y.checked_sub(self.quiet_zone),
+            ) {
+                (Some(x), Some(y)) if x < code_width && y < code_width => {
+                    modules[(y * code_width + x) as usize] == Module::Dark
+                }
+                _ => false,
+            };
+            dark != self.inverted
+        };
+
+        let mut text = String::new();
+        let rows = if self.compact { size.div_ceil(2) } else { size };
+        for row in 0..rows {
+            for x in 0..size {
+                if !self.compact {
+                    let cell = match (self.ansi, filled(x, row)) {
+                        (true, true) => "\x1b[40m  ",
+                        (true, false) => "\x1b[107m  ",
+                        (false, true) => "\u{2588}\u{2588}",
+                        (false, false) => "  ",
+                    };
+                    text.push_str(cell);
+                    continue;
+                }
+                let top = filled(x, 2 * row);
+                let bottom = 2 * row + 1 < size && filled(x, 2 * row + 1);
+                if self.ansi {
+                    // The upper half block is drawn in the colour of the top module, on the
+                    // colour of the bottom module. The padding row below the code is light.
+                    let bottom = bottom || (2 * row + 1 == size && self.inverted);
+                    text.push_str(match (top, bottom) {
+                        (true, true) => "\x1b[30;40m\u{2580}",
+                        (true, false) => "\x1b[30;107m\u{2580}",
+                        (false, true) => "\x1b[97;40m\u{2580}",
+                        (false, false) => "\x1b[97;107m\u{2580}",
+                    });
+                } else {
+                    text.push(match (top, bottom) {
+                        (true, true) => '\u{2588}',
+                        (true, false) => '\u{2580}',
+                        (false, true) => '\u{2584}',
+                        (false, false) => ' ',
+                    });
+                }
+            }
+            if self.ansi {
+                text.push_str("\x1b[0m");
+            }
+            text.push('\n');
+        }
+        if self.uri {
+            text.push_str(data);
+            text.push('\n');
+        }
+        Ok(text)
+    }
+}
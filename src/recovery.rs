@@ -0,0 +1,346 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::{GAError, Result};
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::result;
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
+
+/// cbindgen:ignore
+/// The characters of recovery codes, Crockford's base32 without the easily confused `i`, `l`,
+/// `o` and `u`.
+const RECOVERY_CODE_ALPHABET: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+/// cbindgen:ignore
+const RECOVERY_CODE_GROUP_LEN: usize = 4;
+/// cbindgen:ignore
+const RECOVERY_CODE_SALT_LEN: usize = 16;
+/// cbindgen:ignore
+const RECOVERY_CODE_HASH_LEN: usize = 32;
+/// cbindgen:ignore
+const RECOVERY_CODE_HASH_PREFIX: &str = "$pbkdf2-sha256$i=";
+
+/// Generates, hashes and redeems recovery codes, which let users log in once when they lost
+/// their authenticator.
+///
+/// Codes are drawn from a `CryptoRng` and written in groups of 4 lower case characters, like
+/// `7k2m-x9qd-4hvt`. Only their hashes should be stored: the codes of an account are hashed with
+/// a salt using PBKDF2-HMAC-SHA256. When a code is entered, dashes and whitespace are ignored, and
+/// the characters that are easily confused with others are read as the digits they look like.
+///
+/// ### Example
+/// ```rust
+/// use google_authenticator::{RecoveryCodeStore, RecoveryCodes};
+///
+/// // Use the default number of iterations outside of examples.
+/// let recovery = RecoveryCodes::new().with_count(5).with_iterations(1_000);
+/// let codes = recovery.generate().unwrap();
+/// let mut store = recovery.hash_all(&codes).unwrap();
+///
+/// assert_eq!(Ok(Some(4)), recovery.redeem(&mut store, &codes[0]));
+/// assert_eq!(Ok(None), recovery.redeem(&mut store, &codes[0]));
+/// assert_eq!(Ok(4), store.remaining());
+/// ```
+#[derive(Clone, Debug)]
+pub struct RecoveryCodes {
+    count: usize,
+    length: usize,
+    iterations: u32,
+}
+
+impl Default for RecoveryCodes {
+    fn default() -> Self {
+        RecoveryCodes {
+            count: 10,
+            length: 12,
+            iterations: 100_000,
+        }
+    }
+}
+
+impl RecoveryCodes {
+    /// Create new recovery codes settings, which generate 10 codes of 12 characters (60 bits)
+    /// and hash them with 100000 PBKDF2 iterations.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of codes that `generate` returns.
+    pub fn with_count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Set the number of characters of each code, without dashes. Each character carries 5 bits
+    /// of entropy. It must be between 8 and 32.
+    pub fn with_length(mut self, length: usize) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Set the number of PBKDF2 iterations of new hashes. Existing hashes keep the number of
+    /// iterations they were created with. It must be at least 1.
+    pub fn with_iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Generates the configured number of codes with the random number generator of the
+    /// operating system.
+    pub fn generate(&self) -> Result<Vec<String>> {
+        self.generate_with_rng(&mut OsRng)
+    }
+
+    /// Generates the configured number of codes with a cryptographically secure random number
+    /// generator.
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Result<Vec<String>> {
+        if !(8..=32).contains(&self.length) {
+            return Err(GAError::Error(
+                "bad recovery code length. must be between 8 and 32",
+            ));
+        }
+        let mut bytes = vec![0; self.length];
+        let codes = (0..self.count)
+            .map(|_| {
+                rng.try_fill_bytes(&mut bytes)
+                    .map_err(|_| GAError::Error("random number generator failed"))?;
+                let mut code = String::with_capacity(self.length * 5 / 4);
+                for (i, byte) in bytes.iter().enumerate() {
+                    if i > 0 && i % RECOVERY_CODE_GROUP_LEN == 0 {
+                        code.push('-');
+                    }
+                    // 256 is a multiple of 32, so every character is equally likely.
+                    code.push(RECOVERY_CODE_ALPHABET[(byte & 0x1F) as usize] as char);
+                }
+                Ok(code)
+            })
+            .collect();
+        bytes.zeroize();
+        codes
+    }
+
+    /// Hashes a code for storage, with a random salt from the operating system. The hash is
+    /// written like `$pbkdf2-sha256$i=100000$<salt>$<hash>`, with unpadded base64 salt and hash.
+    /// Prefer `hash_all` for the codes of an account, which makes `redeem` cheaper.
+    pub fn hash(&self, code: &str) -> Result<String> {
+        self.hash_with_rng(&mut OsRng, code)
+    }
+
+    /// Hashes a code for storage, with a salt from a cryptographically secure random number
+    /// generator.
+    pub fn hash_with_rng<R: RngCore + CryptoRng>(&self, rng: &mut R, code: &str) -> Result<String> {
+        let salt = self.salt(rng)?;
+        Ok(self.hash_with_salt(code, &salt))
+    }
+
+    /// Hashes all codes of an account for storage, with one random salt from the operating
+    /// system. As codes with the same salt are checked with a single key derivation, redeeming a
+    /// code costs the same as checking one hash, whatever the number of codes.
+    pub fn hash_all<S: AsRef<str>>(&self, codes: &[S]) -> Result<Vec<String>> {
+        self.hash_all_with_rng(&mut OsRng, codes)
+    }
+
+    /// Hashes all codes of an account for storage, with one salt from a cryptographically secure
+    /// random number generator.
+    pub fn hash_all_with_rng<R: RngCore + CryptoRng, S: AsRef<str>>(
+        &self,
+        rng: &mut R,
+        codes: &[S],
+    ) -> Result<Vec<String>> {
+        let salt = self.salt(rng)?;
+        Ok(codes
+            .iter()
+            .map(|code| self.hash_with_salt(code.as_ref(), &salt))
+            .collect())
+    }
+
+    fn salt<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Result<[u8; RECOVERY_CODE_SALT_LEN]> {
+        if self.iterations == 0 {
+            return Err(GAError::Error("bad iterations. must be at least 1"));
+        }
+        let mut salt = [0; RECOVERY_CODE_SALT_LEN];
+        rng.try_fill_bytes(&mut salt)
+            .map_err(|_| GAError::Error("random number generator failed"))?;
+        Ok(salt)
+    }
+
+    fn hash_with_salt(&self, code: &str, salt: &[u8]) -> String {
+        let hash = Self::derive(code, salt, self.iterations);
+        format!(
+            "{}{}${}${}",
+            RECOVERY_CODE_HASH_PREFIX,
+            self.iterations,
+            STANDARD_NO_PAD.encode(salt),
+            STANDARD_NO_PAD.encode(hash)
+        )
+    }
+
+    /// Checks whether a code matches a hash created by `hash`, in constant time. Malformed
+    /// hashes never match.
+    pub fn verify(&self, code: &str, hash: &str) -> bool {
+        self.matches(code, hash).into()
+    }
+
+    /// Redeems a code against the hashes in `store`. Every stored hash is checked, and the
+    /// matching one is removed, so that the code is accepted only once. Returns the number of
+    /// remaining codes if the code was accepted, or `None` if it did not match or was already
+    /// used.
+    ///
+    /// The key is derived once for each distinct salt in the store, so hashes created by
+    /// `hash_all` cost a single derivation, while hashes created one by one with `hash` cost one
+    /// each. As every attempt costs a full PBKDF2 derivation, callers must rate limit attempts,
+    /// like the `RATE_LIMIT` option of `AuthenticatorFile`.
+    pub fn redeem<S: RecoveryCodeStore + ?Sized>(
+        &self,
+        store: &mut S,
+        code: &str,
+    ) -> result::Result<Option<usize>, S::Error> {
+        let mut derived: Vec<(u32, Vec<u8>, [u8; RECOVERY_CODE_HASH_LEN])> = Vec::new();
+        let mut matched = None;
+        for hash in store.hashes()? {
+            let Some((iterations, salt, expected)) = Self::parse_hash(&hash) else {
+                continue;
+            };
+            let index = match derived
+                .iter()
+                .position(|(i, s, _)| *i == iterations && *s == salt)
+            {
+                Some(index) => index,
+                None => {
+                    let key = Self::derive(code, &salt, iterations);
+                    derived.push((iterations, salt, key));
+                    derived.len() - 1
+                }
+            };
+            if bool::from(derived[index].2.ct_eq(&expected[..])) && matched.is_none() {
+                matched = Some(hash);
+            }
+        }
+        for (_, _, key) in &mut derived {
+            key.zeroize();
+        }
+        match matched {
+            Some(hash) if store.remove(&hash)? => Ok(Some(store.remaining()?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn matches(&self, code: &str, hash: &str) -> Choice {
+        let Some((iterations, salt, expected)) = Self::parse_hash(hash) else {
+            return Choice::from(0);
+        };
+        Self::derive(code, &salt, iterations).ct_eq(&expected[..])
+    }
+
+    fn parse_hash(hash: &str) -> Option<(u32, Vec<u8>, Vec<u8>)> {
+        let mut parts = hash.strip_prefix(RECOVERY_CODE_HASH_PREFIX)?.split('$');
+        let iterations = parts.next()?.parse().ok().filter(|i| *i > 0)?;
+        let salt = STANDARD_NO_PAD.decode(parts.next()?).ok()?;
+        let expected = STANDARD_NO_PAD.decode(parts.next()?).ok()?;
+        if parts.next().is_some() || expected.len() != RECOVERY_CODE_HASH_LEN {
+            return None;
+        }
+        Some((iterations, salt, expected))
+    }
+
+    fn derive(code: &str, salt: &[u8], iterations: u32) -> [u8; RECOVERY_CODE_HASH_LEN] {
+        let mut code = Self::normalize(code);
+        let mut hash = [0; RECOVERY_CODE_HASH_LEN];
+        pbkdf2::pbkdf2_hmac::<Sha256>(code.as_bytes(), salt, iterations, &mut hash);
+        code.zeroize();
+        hash
+    }
+
+    fn normalize(code: &str) -> String {
+        code.chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .map(|c| match c.to_ascii_lowercase() {
+                'i' | 'l' => '1',
+                'o' => '0',
+                c => c,
+            })
+            .collect()
+    }
+}
+
+/// The storage of the hashes of the unused recovery codes of an account, for example a
+/// database table. It is implemented for `Vec<String>` and `HashSet<String>`.
+pub trait RecoveryCodeStore {
+    /// The error of the storage.
+    type Error;
+
+    /// The hashes of the unused codes.
+    fn hashes(&self) -> result::Result<Vec<String>, Self::Error>;
+
+    /// Removes the hash of a redeemed code. Returns `false` if the hash was already removed, for
+    /// example by a concurrent redemption of the same code, in which case the code is rejected.
+    fn remove(&mut self, hash: &str) -> result::Result<bool, Self::Error>;
+
+    /// The number of unused codes.
+    fn remaining(&self) -> result::Result<usize, Self::Error> {
+        Ok(self.hashes()?.len())
+    }
+}
+
+impl RecoveryCodeStore for Vec<String> {
+    type Error = Infallible;
+
+    fn hashes(&self) -> result::Result<Vec<String>, Self::Error> {
+        Ok(self.clone())
+    }
+
+    fn remove(&mut self, hash: &str) -> result::Result<bool, Self::Error> {
+        match self.iter().position(|h| h == hash) {
+            Some(index) => {
+                Vec::remove(self, index);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn remaining(&self) -> result::Result<usize, Self::Error> {
+        Ok(self.len())
+    }
+}
+
+impl RecoveryCodeStore for HashSet<String> {
+    type Error = Infallible;
+
+    fn hashes(&self) -> result::Result<Vec<String>, Self::Error> {
+        Ok(self.iter().cloned().collect())
+    }
+
+    fn remove(&mut self, hash: &str) -> result::Result<bool, Self::Error> {
+        Ok(HashSet::remove(self, hash))
+    }
+
+    fn remaining(&self) -> result::Result<usize, Self::Error> {
+        Ok(self.len())
+    }
+}
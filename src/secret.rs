@@ -0,0 +1,406 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::authenticator::{SECRET_MAX_BYTES, SECRET_MIN_BYTES};
+use crate::{Algorithm, GAError, GoogleAuthenticator, Result};
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use rand::{CryptoRng, RngCore};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use std::borrow::Cow;
+use std::fmt;
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// cbindgen:ignore
+/// The number of bits of entropy of generated secrets, as recommended by RFC 4226.
+pub const DEFAULT_SECRET_BITS: usize = 160;
+
+/// A secret key in its raw, decoded form. Unlike `GoogleAuthenticator::create_secret`, which picks
+/// a number of base32 characters, a `Secret` is generated with a number of bits of entropy, and
+/// its base32 encoding can always be decoded again.
+///
+/// The key is zeroed when the `Secret` is dropped, and it is never shown by `Debug` or `Display`.
+/// It can only be read with the explicit `expose_*` methods. With the feature flag `secrecy`,
+/// `Secret` also implements `secrecy::ExposeSecret`.
+///
+/// ### Example
+/// ```rust
+/// use google_authenticator::{GoogleAuthenticator, Secret};
+///
+/// let secret = Secret::generate(160).unwrap();
+/// let encoded = secret.expose_base32();
+/// assert_eq!(32, encoded.len());
+/// assert_eq!("Secret(***)", format!("{:?}", secret));
+///
+/// let authenticator = GoogleAuthenticator::new();
+/// let code = authenticator.get_code(&secret, 0).unwrap();
+/// assert!(authenticator.verify_code(&encoded, &code, 1, 0));
+/// ```
+#[derive(Clone)]
+pub struct Secret {
+    bytes: Vec<u8>,
+}
+
+impl Secret {
+    /// Generates a new secret with the given number of bits from the random number generator of
+    /// the operating system. `bits` must be a multiple of 8, between 80 and 640.
+    pub fn generate(bits: usize) -> Result<Self> {
+        Self::generate_with_rng(&mut OsRng, bits)
+    }
+
+    /// Generates a new secret with the given number of bits from a cryptographically secure
+    /// random number generator. `bits` must be a multiple of 8, between 80 and 640.
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R, bits: usize) -> Result<Self> {
+        if bits % 8 != 0 {
+            return Err(GAError::Error(
+                "bad secret size. bits must be a multiple of 8",
+            ));
+        }
+        let mut bytes = vec![0; bits / 8];
+        Self::check_len(&bytes)?;
+        rng.try_fill_bytes(&mut bytes)
+            .map_err(|_| GAError::Error("random number generator failed"))?;
+        Ok(Secret { bytes })
+    }
+
+    /// Create a secret from its raw bytes, which must be between 10 and 80 bytes long.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::check_len(bytes)?;
+        Ok(Secret {
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    /// Create a secret from its base32 encoding. Like everywhere else in this crate, the secret
+    /// may be in any case, grouped with spaces or dashes, and with or without padding.
+    pub fn from_base32(secret: &str) -> Result<Self> {
+        Ok(Secret {
+            bytes: GoogleAuthenticator::base32_decode(secret)?,
+        })
+    }
+
+    /// Create a secret from its hex encoding, as used by `oathtool` and many hardware token
+    /// vendors. The secret may be in any case and contain whitespace.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::Secret;
+    ///
+    /// let secret = Secret::from_hex("3132333435363738393031323334353637383930").unwrap();
+    /// assert_eq!("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ", secret.expose_base32());
+    /// ```
+    pub fn from_hex(secret: &str) -> Result<Self> {
+        let mut secret: String = secret.chars().filter(|c| !c.is_whitespace()).collect();
+        let bytes = hex::decode(&secret);
+        secret.zeroize();
+        Self::from_vec(bytes.map_err(|_| GAError::Error("secret must be hex decodeable."))?)
+    }
+
+    /// Create a secret from its base64 encoding, as used by Steam and some vault exports. Both
+    /// the standard and the URL safe alphabet are accepted, with or without padding, and the
+    /// secret may contain whitespace.
+    pub fn from_base64(secret: &str) -> Result<Self> {
+        let mut secret: String = secret
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c {
+                '-' => '+',
+                '_' => '/',
+                c => c,
+            })
+            .collect();
+        let bytes = STANDARD_NO_PAD.decode(secret.trim_end_matches('='));
+        secret.zeroize();
+        Self::from_vec(bytes.map_err(|_| GAError::Error("secret must be base64 decodeable."))?)
+    }
+
+    /// Normalises a base32 secret that was entered by a user or imported, by removing spaces and
+    /// dashes, upper casing it and fixing its padding. An error is returned if the secret can not
+    /// be decoded, or if it is not between 80 and 640 bits long.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::Secret;
+    ///
+    /// assert_eq!(
+    ///     "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA====",
+    ///     Secret::normalize("gezd gnbv gy3t qojq gezd gnbv gy3t qojq gez-a").unwrap()
+    /// );
+    /// ```
+    pub fn normalize(secret: &str) -> Result<String> {
+        Ok(Self::from_base32(secret)?.expose_base32())
+    }
+
+    /// The raw bytes of the secret.
+    pub fn expose_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The secret encoded as base32, padded to a multiple of 8 characters. The returned string is
+    /// not zeroed when dropped.
+    pub fn expose_base32(&self) -> String {
+        base32::encode(base32::Alphabet::RFC4648 { padding: true }, &self.bytes)
+    }
+
+    /// The secret encoded as lower case hex. The returned string is not zeroed when dropped.
+    pub fn expose_hex(&self) -> String {
+        hex::encode(&self.bytes)
+    }
+
+    /// The secret encoded as padded standard base64. The returned string is not zeroed when
+    /// dropped.
+    pub fn expose_base64(&self) -> String {
+        STANDARD.encode(&self.bytes)
+    }
+
+    fn from_vec(mut bytes: Vec<u8>) -> Result<Self> {
+        if let Err(err) = Self::check_len(&bytes) {
+            bytes.zeroize();
+            return Err(err);
+        }
+        Ok(Secret { bytes })
+    }
+
+    fn check_len(bytes: &[u8]) -> Result<()> {
+        if bytes.len() < SECRET_MIN_BYTES || bytes.len() > SECRET_MAX_BYTES {
+            return Err(GAError::Error(
+                "bad secret size. must be between 80 and 640 bits, recommend 160",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Secret {}
+
+/// Compares the keys in constant time. Only their lengths may be leaked.
+impl PartialEq for Secret {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes.ct_eq(&other.bytes).into()
+    }
+}
+
+impl Eq for Secret {}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+#[cfg(feature = "secrecy")]
+impl secrecy::ExposeSecret<[u8]> for Secret {
+    fn expose_secret(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl ToPreparedSecret for Secret {
+    fn to_prepared_secret(&self, algorithm: Algorithm) -> Result<Cow<'_, PreparedSecret>> {
+        Ok(Cow::Owned(PreparedSecret::per_call(
+            self.bytes.clone(),
+            algorithm,
+        )))
+    }
+}
+
+/// HMAC accepts keys of any length, so keying it can not fail.
+/// cbindgen:ignore
+const ANY_SIZE: &str = "hmac accepts keys of any size";
+
+/// A secret that is validated and decoded once, with the inner and outer HMAC pad state
+/// computed up front. Generating or verifying codes with a `PreparedSecret` skips the base32
+/// decoding and HMAC key setup, which otherwise happen for every call.
+///
+/// This trades wiping for speed: the decoded key is zeroed when it is dropped, but the HMAC
+/// state, from which codes can be generated just like from the key, is not. Strings and `Secret`
+/// do not keep any HMAC state; they key the HMAC for every code from the decoded key, which is
+/// zeroed after the call.
+///
+/// ### Example
+/// ```rust
+/// use google_authenticator::{Algorithm, GoogleAuthenticator, PreparedSecret};
+///
+/// let secret = PreparedSecret::new("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", Algorithm::Sha1).unwrap();
+/// let authenticator = GoogleAuthenticator::new();
+/// assert!(authenticator.verify_code(&secret, "224124", 3, 1523610659 / 30));
+/// ```
+#[derive(Clone)]
+pub struct PreparedSecret {
+    key: Vec<u8>,
+    algorithm: Algorithm,
+    /// The precomputed HMAC state, or `None` if the HMAC is keyed for every code.
+    mac: Option<PreparedMac>,
+}
+
+#[derive(Clone)]
+enum PreparedMac {
+    Sha1(Hmac<Sha1>),
+    Sha256(Hmac<Sha256>),
+    Sha512(Hmac<Sha512>),
+}
+
+impl PreparedSecret {
+    /// Validates and decodes the base32 encoded `secret`, and prepares it for use with the given
+    /// algorithm.
+    pub fn new(secret: &str, algorithm: Algorithm) -> Result<Self> {
+        let key = GoogleAuthenticator::base32_decode(secret)?;
+        Ok(Self::from_key(key, algorithm))
+    }
+
+    fn from_key(key: Vec<u8>, algorithm: Algorithm) -> Self {
+        let mac = match algorithm {
+            Algorithm::Sha1 => PreparedMac::Sha1(Hmac::new_from_slice(&key).expect(ANY_SIZE)),
+            Algorithm::Sha256 => PreparedMac::Sha256(Hmac::new_from_slice(&key).expect(ANY_SIZE)),
+            Algorithm::Sha512 => PreparedMac::Sha512(Hmac::new_from_slice(&key).expect(ANY_SIZE)),
+        };
+        PreparedSecret {
+            key,
+            algorithm,
+            mac: Some(mac),
+        }
+    }
+
+    /// A secret for a single call, which keys the HMAC for every code instead of keeping its
+    /// state, so nothing but the zeroed key is derived from the secret.
+    pub(crate) fn per_call(key: Vec<u8>, algorithm: Algorithm) -> Self {
+        PreparedSecret {
+            key,
+            algorithm,
+            mac: None,
+        }
+    }
+
+    /// The algorithm this secret was prepared for.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Calculates the code of `code_len` digits for the given counter, as described in RFC 4226.
+    pub(crate) fn hotp(&self, counter: u64, code_len: usize) -> String {
+        let msg_bytes = counter.to_be_bytes();
+        let hash = match (&self.mac, self.algorithm) {
+            (Some(PreparedMac::Sha1(mac)), _) => sign(mac.clone(), &msg_bytes),
+            (Some(PreparedMac::Sha256(mac)), _) => sign(mac.clone(), &msg_bytes),
+            (Some(PreparedMac::Sha512(mac)), _) => sign(mac.clone(), &msg_bytes),
+            (None, Algorithm::Sha1) => sign(
+                Hmac::<Sha1>::new_from_slice(&self.key).expect(ANY_SIZE),
+                &msg_bytes,
+            ),
+            (None, Algorithm::Sha256) => sign(
+                Hmac::<Sha256>::new_from_slice(&self.key).expect(ANY_SIZE),
+                &msg_bytes,
+            ),
+            (None, Algorithm::Sha512) => sign(
+                Hmac::<Sha512>::new_from_slice(&self.key).expect(ANY_SIZE),
+                &msg_bytes,
+            ),
+        };
+        let offset = hash[hash.len() - 1] & 0x0F;
+        let mut truncated_hash: [u8; 4] = Default::default();
+        truncated_hash.copy_from_slice(&hash[offset as usize..(offset + 4) as usize]);
+        let mut code = u32::from_be_bytes(truncated_hash) as u64;
+        code &= 0x7FFFFFFF;
+        code %= 10_u64.pow(code_len as u32);
+        format!("{:0width$}", code, width = code_len)
+    }
+}
+
+fn sign<M: Mac>(mut mac: M, msg: &[u8]) -> Vec<u8> {
+    mac.update(msg);
+    mac.finalize().into_bytes().to_vec()
+}
+
+impl Drop for PreparedSecret {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl fmt::Debug for PreparedSecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PreparedSecret")
+            .field("algorithm", &self.algorithm())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Anything that codes can be generated and verified with. This is implemented for base32
+/// encoded strings, which are decoded on every call, and for `PreparedSecret`, which is only
+/// prepared again if it was prepared for a different algorithm.
+pub trait ToPreparedSecret {
+    /// Returns the secret prepared for the given algorithm.
+    fn to_prepared_secret(&self, algorithm: Algorithm) -> Result<Cow<'_, PreparedSecret>>;
+}
+
+impl ToPreparedSecret for PreparedSecret {
+    fn to_prepared_secret(&self, algorithm: Algorithm) -> Result<Cow<'_, PreparedSecret>> {
+        if self.algorithm() == algorithm {
+            Ok(Cow::Borrowed(self))
+        } else {
+            Ok(Cow::Owned(Self::per_call(self.key.clone(), algorithm)))
+        }
+    }
+}
+
+impl ToPreparedSecret for str {
+    fn to_prepared_secret(&self, algorithm: Algorithm) -> Result<Cow<'_, PreparedSecret>> {
+        let key = GoogleAuthenticator::base32_decode(self)?;
+        Ok(Cow::Owned(PreparedSecret::per_call(key, algorithm)))
+    }
+}
+
+impl ToPreparedSecret for String {
+    fn to_prepared_secret(&self, algorithm: Algorithm) -> Result<Cow<'_, PreparedSecret>> {
+        self.as_str().to_prepared_secret(algorithm)
+    }
+}
+
+#[cfg(feature = "secrecy")]
+impl ToPreparedSecret for secrecy::SecretString {
+    fn to_prepared_secret(&self, algorithm: Algorithm) -> Result<Cow<'_, PreparedSecret>> {
+        use secrecy::ExposeSecret;
+        self.expose_secret().to_prepared_secret(algorithm)
+    }
+}
+
+impl<T: ToPreparedSecret + ?Sized> ToPreparedSecret for &T {
+    fn to_prepared_secret(&self, algorithm: Algorithm) -> Result<Cow<'_, PreparedSecret>> {
+        (**self).to_prepared_secret(algorithm)
+    }
+}
@@ -0,0 +1,434 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::authenticator::{CODE_MAX_LEN, CODE_MIN_LEN};
+#[cfg(any(feature = "qrcode", doc))]
+use crate::ErrorCorrectionLevel;
+use crate::{Algorithm, GAError, GoogleAuthenticator, Result};
+#[cfg(feature = "with-qrcode")]
+use crate::{QrStyle, TerminalQr};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Characters that are percent encoded in labels and parameter values. Everything but the
+/// unreserved characters of RFC 3986 is encoded, which includes the colon that separates the
+/// issuer from the account name.
+const ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Parameters that are read into the fields of `OtpAuthUri`.
+/// cbindgen:ignore
+const STANDARD_PARAMETERS: [&str; 6] = [
+    "secret",
+    "issuer",
+    "algorithm",
+    "digits",
+    "period",
+    "counter",
+];
+
+/// The kind of one-time password that an account uses.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OtpType {
+    /// Time based one-time passwords, as described in RFC 6238.
+    Totp,
+    /// Counter based one-time passwords, as described in RFC 4226.
+    Hotp,
+}
+
+/// An account as described by an `otpauth://` URI, following the
+/// [Key Uri Format](https://github.com/google/google-authenticator/wiki/Key-Uri-Format) of Google
+/// Authenticator. Parameters that are missing from the URI are set to their defaults.
+///
+/// An `OtpAuthUri` can be parsed from a string, or built with `OtpAuthUri::new` and the `with_*`
+/// methods, and is formatted back to a URI with `to_string`.
+///
+/// ### Example
+/// ```rust
+/// use google_authenticator::{Algorithm, OtpAuthUri, OtpType};
+///
+/// let uri: OtpAuthUri = "otpauth://totp/ACME%20Co:john@example.com?secret=I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3&issuer=ACME%20Co&algorithm=SHA256"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(OtpType::Totp, uri.otp_type);
+/// assert_eq!("john@example.com", uri.account);
+/// assert_eq!(Some("ACME Co".to_string()), uri.issuer);
+/// assert_eq!(Algorithm::Sha256, uri.algorithm);
+///
+/// let code = uri.authenticator().get_code(&uri.secret, 0).unwrap();
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct OtpAuthUri {
+    /// Whether the account uses time or counter based codes.
+    pub otp_type: OtpType,
+    /// The base32 encoded secret.
+    pub secret: String,
+    /// The name of the account, without the issuer prefix of the label.
+    pub account: String,
+    /// The provider or service the account belongs to, if any.
+    pub issuer: Option<String>,
+    /// The hash function used to generate codes.
+    pub algorithm: Algorithm,
+    /// The number of digits of a code.
+    pub digits: usize,
+    /// The number of seconds a time based code is valid.
+    pub period: u64,
+    /// The initial counter of a counter based account.
+    pub counter: u64,
+    /// All other parameters of the URI, in the order they appeared in.
+    pub parameters: Vec<(String, String)>,
+}
+
+impl OtpAuthUri {
+    /// Creates a URI for a time based account with the given base32 encoded secret and account
+    /// name, using the default algorithm, digits and period.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::{Algorithm, OtpAuthUri};
+    ///
+    /// let uri = OtpAuthUri::new("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", "john@example.com")
+    ///     .with_issuer("ACME Co")
+    ///     .with_algorithm(Algorithm::Sha256)
+    ///     .with_digits(8);
+    /// assert_eq!(
+    ///     "otpauth://totp/ACME%20Co:john%40example.com?secret=I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=30",
+    ///     uri.to_string(),
+    /// );
+    /// ```
+    pub fn new(secret: &str, account: &str) -> Self {
+        OtpAuthUri {
+            otp_type: OtpType::Totp,
+            secret: secret.to_string(),
+            account: account.to_string(),
+            issuer: None,
+            algorithm: Algorithm::default(),
+            digits: 6,
+            period: 30,
+            counter: 0,
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Use this method to set the provider or service the account belongs to. It is used both as
+    /// prefix of the label and as `issuer` parameter.
+    pub fn with_issuer(mut self, issuer: &str) -> Self {
+        self.issuer = Some(issuer.to_string());
+        self
+    }
+
+    /// Use this method to set the hash function used to generate codes.
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Use this method to set the number of digits of a code, which `validate` requires to be
+    /// between 6 and 10.
+    pub fn with_digits(mut self, digits: usize) -> Self {
+        self.digits = digits;
+        self
+    }
+
+    /// Use this method to set the time a time based code is valid. Only whole seconds are used, and
+    /// `validate` requires at least one.
+    pub fn with_period(mut self, period: Duration) -> Self {
+        self.period = period.as_secs();
+        self
+    }
+
+    /// Use this method to turn the URI into one for a counter based account, starting at the
+    /// given counter.
+    pub fn with_counter(mut self, counter: u64) -> Self {
+        self.otp_type = OtpType::Hotp;
+        self.counter = counter;
+        self
+    }
+
+    /// Use this method to add a parameter that is not part of the standard set, like the `image`
+    /// parameter that some apps use to show a logo.
+    pub fn with_parameter(mut self, key: &str, value: &str) -> Self {
+        self.parameters.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Checks that this URI can be turned into a string that parses back into the same account:
+    /// the secret must be valid base32, the account name must neither be empty nor start with
+    /// whitespace, the number of digits must be between 6 and 10, the period at least one second,
+    /// and extra parameters must not use the name of a standard one. The functions that render
+    /// QR codes call it before turning the URI into a string.
+    ///
+    /// ### Example
+    /// ```rust
+    /// use google_authenticator::OtpAuthUri;
+    ///
+    /// let uri = OtpAuthUri::new("I3VFM3JKMNDJCDH5BMBEEQAW6KJ6NOE3", "john@example.com");
+    /// assert!(uri.validate().is_ok());
+    /// assert!(uri.with_digits(4).validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<()> {
+        if self.secret.is_empty() {
+            return Err(GAError::Error("uri must contain a secret"));
+        }
+        GoogleAuthenticator::base32_decode(&self.secret)?;
+        if self.account.is_empty() {
+            return Err(GAError::Error("uri must contain an account name"));
+        }
+        if self.account.starts_with(char::is_whitespace) {
+            return Err(GAError::Error(
+                "account name must not start with whitespace",
+            ));
+        }
+        if self.digits < CODE_MIN_LEN || self.digits > CODE_MAX_LEN {
+            return Err(GAError::Error("digits must be between 6 and 10"));
+        }
+        if self.period == 0 {
+            return Err(GAError::Error("period must be at least one second"));
+        }
+        if self
+            .parameters
+            .iter()
+            .any(|(key, _)| STANDARD_PARAMETERS.contains(&key.as_str()))
+        {
+            return Err(GAError::Error("parameter must not be a standard parameter"));
+        }
+        Ok(())
+    }
+
+    /// Creates a `GoogleAuthenticator` that generates and verifies the codes of this account.
+    pub fn authenticator(&self) -> GoogleAuthenticator {
+        GoogleAuthenticator::new()
+            .with_algorithm(self.algorithm)
+            .with_code_length(self.digits)
+            .with_period(Duration::from_secs(self.period))
+    }
+
+    /// Get a `data:image/svg+xml;base64,` URL with the QR code of this URI. See
+    /// `GoogleAuthenticator::qr_code_url` for the parameters.
+    #[cfg(any(feature = "qrcode", doc))]
+    pub fn qr_code_url(
+        &self,
+        width: u32,
+        height: u32,
+        level: ErrorCorrectionLevel,
+    ) -> Result<String> {
+        self.validate()?;
+        GoogleAuthenticator::svg_data_url(&self.to_string(), width, height, level)
+    }
+
+    /// Creates an in-memory SVG file with the QR code of this URI. See
+    /// `GoogleAuthenticator::qr_code` for the parameters.
+    #[cfg(any(feature = "with-qrcode", doc))]
+    pub fn qr_code(&self, width: u32, height: u32, level: ErrorCorrectionLevel) -> Result<String> {
+        self.validate()?;
+        GoogleAuthenticator::svg(&self.to_string(), width, height, level)
+    }
+
+    /// Creates an in-memory PNG image with the QR code of this URI. See
+    /// `GoogleAuthenticator::qr_code` for the parameters.
+    #[cfg(feature = "with-qrcode")]
+    pub fn qr_code_png(
+        &self,
+        width: u32,
+        height: u32,
+        level: ErrorCorrectionLevel,
+    ) -> Result<Vec<u8>> {
+        self.validate()?;
+        QrStyle::default().png(&self.to_string(), width, height, level)
+    }
+
+    /// Renders the QR code of this URI as text for a terminal. See
+    /// `GoogleAuthenticator::qr_code_terminal` for the parameters.
+    #[cfg(feature = "with-qrcode")]
+    pub fn qr_code_terminal(&self, level: ErrorCorrectionLevel) -> Result<String> {
+        self.validate()?;
+        TerminalQr::default().render(&self.to_string(), level)
+    }
+}
+
+/// Shows all fields except the secret, like `Secret`.
+impl fmt::Debug for OtpAuthUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OtpAuthUri")
+            .field("otp_type", &self.otp_type)
+            .field("secret", &format_args!("***"))
+            .field("account", &self.account)
+            .field("issuer", &self.issuer)
+            .field("algorithm", &self.algorithm)
+            .field("digits", &self.digits)
+            .field("period", &self.period)
+            .field("counter", &self.counter)
+            .field("parameters", &self.parameters)
+            .finish()
+    }
+}
+
+impl fmt::Display for OtpAuthUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let otp_type = match self.otp_type {
+            OtpType::Totp => "totp",
+            OtpType::Hotp => "hotp",
+        };
+        write!(f, "otpauth://{}/", otp_type)?;
+        if let Some(issuer) = &self.issuer {
+            write!(f, "{}:", utf8_percent_encode(issuer, ENCODE_SET))?;
+        }
+        write!(
+            f,
+            "{}?secret={}",
+            utf8_percent_encode(&self.account, ENCODE_SET),
+            utf8_percent_encode(&self.secret, ENCODE_SET)
+        )?;
+        if let Some(issuer) = &self.issuer {
+            write!(f, "&issuer={}", utf8_percent_encode(issuer, ENCODE_SET))?;
+        }
+        write!(f, "&algorithm={}&digits={}", self.algorithm, self.digits)?;
+        match self.otp_type {
+            OtpType::Totp => write!(f, "&period={}", self.period)?,
+            OtpType::Hotp => write!(f, "&counter={}", self.counter)?,
+        }
+        for (key, value) in &self.parameters {
+            write!(
+                f,
+                "&{}={}",
+                utf8_percent_encode(key, ENCODE_SET),
+                utf8_percent_encode(value, ENCODE_SET)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for OtpAuthUri {
+    type Err = GAError;
+
+    fn from_str(uri: &str) -> Result<Self> {
+        let rest = strip_prefix_ignore_case(uri, "otpauth://")
+            .ok_or(GAError::Error("uri must start with otpauth://"))?;
+        let (otp_type, rest) = if let Some(rest) = strip_prefix_ignore_case(rest, "totp/") {
+            (OtpType::Totp, rest)
+        } else if let Some(rest) = strip_prefix_ignore_case(rest, "hotp/") {
+            (OtpType::Hotp, rest)
+        } else {
+            return Err(GAError::Error("uri type must be totp or hotp"));
+        };
+        let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        // The issuer and account name are separated by a literal or an encoded colon. A literal
+        // colon takes precedence, so that encoded colons in the account name survive a round
+        // trip. An encoded colon is only a separator if there is an issuer parameter, preferably
+        // the one whose prefix matches it; otherwise it is part of the account name.
+        let issuer_parameter = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("issuer="))
+            .map(decode)
+            .transpose()?;
+        let (label_issuer, account) = match label.split_once(':') {
+            Some((issuer, account)) => (Some(decode(issuer)?), decode(account)?),
+            None => match &issuer_parameter {
+                Some(issuer) => {
+                    let separators = find_all_ignore_case(label, "%3A");
+                    let mut matching = separators.iter().copied().filter(|&index| {
+                        decode(&label[..index]).is_ok_and(|prefix| prefix == *issuer)
+                    });
+                    match matching.next().or_else(|| separators.first().copied()) {
+                        Some(index) => {
+                            (Some(decode(&label[..index])?), decode(&label[index + 3..])?)
+                        }
+                        None => (None, decode(label)?),
+                    }
+                }
+                None => (None, decode(label)?),
+            },
+        };
+        let account = account.trim_start();
+        if account.is_empty() {
+            return Err(GAError::Error("uri must contain an account name"));
+        }
+
+        let mut result = OtpAuthUri::new("", account);
+        result.otp_type = otp_type;
+        result.issuer = label_issuer;
+        let mut has_counter = false;
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = decode(value)?;
+            match key {
+                "secret" => result.secret = value,
+                // The issuer parameter takes precedence over the prefix of the label.
+                "issuer" => result.issuer = Some(value),
+                "algorithm" => result.algorithm = value.parse()?,
+                "digits" => {
+                    result.digits = value
+                        .parse()
+                        .map_err(|_| GAError::Error("digits must be a number"))?;
+                }
+                "period" => {
+                    result.period = value
+                        .parse()
+                        .map_err(|_| GAError::Error("period must be a number"))?;
+                }
+                "counter" => {
+                    result.counter = value
+                        .parse()
+                        .map_err(|_| GAError::Error("counter must be a number"))?;
+                    has_counter = true;
+                }
+                _ => result.parameters.push((decode(key)?, value)),
+            }
+        }
+
+        result.validate()?;
+        if otp_type == OtpType::Hotp && !has_counter {
+            return Err(GAError::Error("hotp uri must contain a counter"));
+        }
+        Ok(result)
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    match s.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&s[prefix.len()..]),
+        _ => None,
+    }
+}
+
+fn find_all_ignore_case(s: &str, needle: &str) -> Vec<usize> {
+    s.as_bytes()
+        .windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| window.eq_ignore_ascii_case(needle.as_bytes()))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+fn decode(s: &str) -> Result<String> {
+    match percent_decode_str(s).decode_utf8() {
+        Ok(decoded) => Ok(decoded.into_owned()),
+        Err(_) => Err(GAError::Error("uri must be valid percent encoded utf-8")),
+    }
+}
@@ -0,0 +1,67 @@
+// MIT License
+//
+// Copyright (c) 2018 hanskorg
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::{error, fmt};
+
+/// The outcome of a successful verification, returned by
+/// `GoogleAuthenticator::verify_code_detailed`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Verification {
+    /// The time slice whose code matched.
+    pub time_slice: u64,
+    /// The number of periods between the matched time slice and the current one. A positive
+    /// offset means that the clock of the user is ahead, a negative one that it is behind.
+    pub offset: i64,
+}
+
+/// Represents the reasons why `GoogleAuthenticator::verify_code_detailed` can reject a code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    /// The code does not have the configured number of digits.
+    WrongLength,
+    /// The code contains characters that are not digits.
+    NonNumeric,
+    /// The code does not match any time slice within the window.
+    OutsideWindow,
+    /// The code matches a time slice that was already accepted before.
+    Reused,
+    /// The secret has a bad length or is not base32 decodeable.
+    BadSecret,
+    /// The code length or period of the `GoogleAuthenticator` is invalid.
+    BadConfiguration,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match self {
+            Rejection::WrongLength => "code has the wrong length",
+            Rejection::NonNumeric => "code must only contain digits",
+            Rejection::OutsideWindow => "code does not match any time slice in the window",
+            Rejection::Reused => "code was already used",
+            Rejection::BadSecret => "secret must be base32 decodeable and of valid length",
+            Rejection::BadConfiguration => "code length or period are invalid",
+        };
+        f.write_str(result)
+    }
+}
+
+impl error::Error for Rejection {}